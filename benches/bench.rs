@@ -1,8 +1,35 @@
-use data_rw;
 use data_rw::{DataOwnedReader, DataReader};
 use serde::{Deserialize, Serialize};
 
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
 
 #[inline]
 fn bench_owned_pack(size: usize) {
@@ -195,6 +222,76 @@ fn bench_foo_bincode(size: usize) {
     }
 }
 
+#[derive(Deserialize, Serialize, Debug, PartialOrd, PartialEq, Clone, Default)]
+struct State {
+    tick: u64,
+    name: String,
+    pos: Vec<f32>,
+    players: Vec<String>,
+}
+
+fn make_state() -> State {
+    State {
+        tick: 1,
+        name: "state".to_string(),
+        pos: (0..256).map(|x| x as f32).collect(),
+        players: (0..32).map(|x| format!("player{}", x)).collect(),
+    }
+}
+
+#[inline]
+fn bench_state(size: usize, buff: &[u8]) {
+    for _ in 0..size {
+        let mut data = DataReader::from(buff);
+        let v: State = data.serde_deserialize().unwrap();
+        assert_eq!(v.tick, 1);
+    }
+}
+
+#[inline]
+fn bench_state_into(size: usize, buff: &[u8], state: &mut State) {
+    for _ in 0..size {
+        let mut data = DataReader::from(buff);
+        data.deserialize_into(state).unwrap();
+        assert_eq!(state.tick, 1);
+    }
+}
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let start = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - start
+}
+
+fn benchmark_in_place(c: &mut Criterion) {
+    let size: usize = 1000;
+    let buff = data_rw::Data::serialize(make_state()).unwrap().into_inner();
+    let mut state = State::default();
+
+    println!(
+        "bench_state allocations per {} decodes: {}",
+        size,
+        count_allocations(|| bench_state(size, &buff))
+    );
+    bench_state_into(1, &buff, &mut state);
+    println!(
+        "bench_state_into allocations per {} decodes: {}",
+        size,
+        count_allocations(|| bench_state_into(size, &buff, &mut state))
+    );
+
+    c.bench_with_input(BenchmarkId::new("bench_state", size), &size, |b, &s| {
+        b.iter(|| bench_state(s, &buff));
+    });
+    c.bench_with_input(
+        BenchmarkId::new("bench_state_into", size),
+        &size,
+        |b, &s| {
+            b.iter(|| bench_state_into(s, &buff, &mut state));
+        },
+    );
+}
+
 fn benchmark(c: &mut Criterion) {
     let size: usize = 1000;
     c.bench_with_input(BenchmarkId::new("bench_base_rw", size), &size, |b, &s| {
@@ -289,5 +386,37 @@ fn benchmark(c: &mut Criterion) {
     );
 }
 
-criterion_group!(benches, benchmark);
+/// nested sequences of unknown length, each one reserves its varint length
+struct Deep(usize);
+
+impl Serialize for Deep {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0 == 0 {
+            serializer.serialize_str("123123")
+        } else {
+            serializer.collect_seq((0..2).filter(|_| true).map(|i| {
+                if i == 0 {
+                    Deep(self.0 - 1)
+                } else {
+                    Deep(0)
+                }
+            }))
+        }
+    }
+}
+
+fn benchmark_deep(c: &mut Criterion) {
+    let config = data_rw::Config::new().len_encoding(data_rw::LenEncoding::Varint);
+    for depth in [16usize, 256, 2048] {
+        c.bench_with_input(
+            BenchmarkId::new("bench_deep_varint", depth),
+            &depth,
+            |b, &d| {
+                b.iter(|| data_rw::Data::serialize_with(config, Deep(d)).unwrap());
+            },
+        );
+    }
+}
+
+criterion_group!(benches, benchmark, benchmark_in_place, benchmark_deep);
 criterion_main!(benches);
@@ -0,0 +1,242 @@
+//! Bit level writing and reading, for flags, small enums and quantized floats.
+//!
+//! Bits fill each byte from its lowest bit up and values are written low bit first.
+//! After `align` the rest of the current byte is zero and byte level writes
+//! such as `write_fixed` can go on through `data()`/`reader()`.
+
+use crate::{Data, DataReader};
+use anyhow::{ensure, Result};
+
+/// Writes bits to the end of a `Data`.
+pub struct BitWriter<'a> {
+    data: &'a mut Data,
+    /// bits used in the last byte, 0 when aligned
+    used: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    #[inline]
+    pub fn new(data: &'a mut Data) -> Self {
+        BitWriter { data, used: 0 }
+    }
+
+    /// write the low `bits` bits of `v`, fails if `v` doesn't fit
+    #[inline]
+    pub fn write_bits(&mut self, v: u64, bits: u32) -> Result<()> {
+        ensure!(bits <= 64, "bit count {} > 64", bits);
+        ensure!(
+            bits == 64 || v >> bits == 0,
+            "{} doesn't fit {} bits",
+            v,
+            bits
+        );
+        let mut v = v;
+        let mut left = bits;
+        while left > 0 {
+            if self.used == 0 {
+                self.data.write_fixed(0u8);
+            }
+            let n = (8 - self.used).min(left);
+            let last = self.data.len() - 1;
+            self.data[last] |= ((v & ((1 << n) - 1)) as u8) << self.used;
+            v >>= n;
+            left -= n;
+            self.used = (self.used + n) % 8;
+        }
+        Ok(())
+    }
+
+    /// write `v` in `bits` bits of two's complement, fails if `v` doesn't fit
+    #[inline]
+    pub fn write_signed(&mut self, v: i64, bits: u32) -> Result<()> {
+        ensure!((1..=64).contains(&bits), "bit count {} not in 1..=64", bits);
+        let sign = v >> (bits - 1);
+        ensure!(sign == 0 || sign == -1, "{} doesn't fit {} bits", v, bits);
+        let v = if bits == 64 {
+            v as u64
+        } else {
+            v as u64 & ((1 << bits) - 1)
+        };
+        self.write_bits(v, bits)
+    }
+
+    #[inline]
+    pub fn write_bool(&mut self, v: bool) -> Result<()> {
+        self.write_bits(v as u64, 1)
+    }
+
+    /// write `v` clamped to `min..=max` as one of `2^bits` evenly spaced steps
+    #[inline]
+    pub fn write_quantized(&mut self, v: f64, min: f64, max: f64, bits: u32) -> Result<()> {
+        let steps = quantize_steps(min, max, bits)?;
+        ensure!(!v.is_nan(), "can't quantize NaN");
+        let v = v.max(min).min(max);
+        let q = ((v - min) / (max - min) * steps as f64).round() as u64;
+        self.write_bits(q.min(steps), bits)
+    }
+
+    /// write one bit per bool, the reader must know their count
+    #[inline]
+    pub fn write_bitmap(&mut self, v: &[bool]) -> Result<()> {
+        for &b in v {
+            self.write_bool(b)?;
+        }
+        Ok(())
+    }
+
+    /// pad the current byte with zeros
+    #[inline]
+    pub fn align(&mut self) {
+        self.used = 0;
+    }
+
+    #[inline]
+    pub fn is_aligned(&self) -> bool {
+        self.used == 0
+    }
+
+    /// align and return the data for byte level writes
+    #[inline]
+    pub fn data(&mut self) -> &mut Data {
+        self.align();
+        self.data
+    }
+}
+
+/// Reads bits written by `BitWriter` from a `DataReader`.
+pub struct BitReader<'r, 'a> {
+    reader: &'r mut DataReader<'a>,
+    byte: u8,
+    /// unread bits of `byte`, 0 when aligned
+    left: u32,
+}
+
+impl<'r, 'a> BitReader<'r, 'a> {
+    #[inline]
+    pub fn new(reader: &'r mut DataReader<'a>) -> Self {
+        BitReader {
+            reader,
+            byte: 0,
+            left: 0,
+        }
+    }
+
+    /// read a `bits` bit unsigned value
+    #[inline]
+    pub fn read_bits(&mut self, bits: u32) -> Result<u64> {
+        ensure!(bits <= 64, "bit count {} > 64", bits);
+        let mut v = 0u64;
+        let mut got = 0;
+        while got < bits {
+            if self.left == 0 {
+                self.byte = self.reader.read_fixed::<u8>()?;
+                self.left = 8;
+            }
+            let n = self.left.min(bits - got);
+            let part = (self.byte >> (8 - self.left)) as u64 & ((1 << n) - 1);
+            v |= part << got;
+            got += n;
+            self.left -= n;
+        }
+        Ok(v)
+    }
+
+    /// read a `bits` bit two's complement value
+    #[inline]
+    pub fn read_signed(&mut self, bits: u32) -> Result<i64> {
+        ensure!((1..=64).contains(&bits), "bit count {} not in 1..=64", bits);
+        let shift = 64 - bits;
+        Ok(((self.read_bits(bits)? << shift) as i64) >> shift)
+    }
+
+    #[inline]
+    pub fn read_bool(&mut self) -> Result<bool> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    /// read a value written by `write_quantized` with the same range and bits
+    #[inline]
+    pub fn read_quantized(&mut self, min: f64, max: f64, bits: u32) -> Result<f64> {
+        let steps = quantize_steps(min, max, bits)?;
+        let q = self.read_bits(bits)?;
+        Ok(min + (max - min) * (q as f64 / steps as f64))
+    }
+
+    /// read `len` bools written by `write_bitmap`
+    #[inline]
+    pub fn read_bitmap(&mut self, len: usize) -> Result<Vec<bool>> {
+        let mut res = Vec::with_capacity(len.min(self.reader.len() * 8 + self.left as usize));
+        for _ in 0..len {
+            res.push(self.read_bool()?);
+        }
+        Ok(res)
+    }
+
+    /// skip the rest of the current byte
+    #[inline]
+    pub fn align(&mut self) {
+        self.left = 0;
+    }
+
+    #[inline]
+    pub fn is_aligned(&self) -> bool {
+        self.left == 0
+    }
+
+    /// align and return the reader for byte level reads
+    #[inline]
+    pub fn reader(&mut self) -> &mut DataReader<'a> {
+        self.align();
+        self.reader
+    }
+}
+
+/// the largest step of a quantized value
+#[inline]
+fn quantize_steps(min: f64, max: f64, bits: u32) -> Result<u64> {
+    ensure!(
+        min < max && (max - min).is_finite(),
+        "invalid quantize range {}..={}",
+        min,
+        max
+    );
+    ensure!(
+        (1..=32).contains(&bits),
+        "quantize bits {} not in 1..=32",
+        bits
+    );
+    Ok((1 << bits) - 1)
+}
+
+impl Data {
+    /// write a bool slice as its varint length and one bit per bool
+    #[inline]
+    pub fn write_bitmap(&mut self, v: &[bool]) {
+        self.write_var_integer(v.len() as u64);
+        let start = self.len();
+        self.resize(start + v.len().div_ceil(8), 0);
+        for (i, &b) in v.iter().enumerate() {
+            if b {
+                self[start + i / 8] |= 1 << (i % 8);
+            }
+        }
+    }
+}
+
+impl<'a> DataReader<'a> {
+    /// read a bool slice written by `write_bitmap`
+    #[inline]
+    pub fn read_bitmap(&mut self) -> Result<Vec<bool>> {
+        let len = self.read_var_integer::<u64>()? as usize;
+        let size = len.div_ceil(8);
+        ensure!(
+            size <= self.len(),
+            "read bitmap size too big,{}>{}",
+            size,
+            self.len()
+        );
+        let (bytes, have) = self.buff.split_at(size);
+        self.buff = have;
+        Ok((0..len).map(|i| bytes[i / 8] >> (i % 8) & 1 == 1).collect())
+    }
+}
@@ -0,0 +1,197 @@
+/// How lengths of strings, bytes, sequences and maps are written,
+/// this also applies to `write_fixed`/`read_fixed_str`/`read_fixed_buf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LenEncoding {
+    /// little endian u32, the original layout
+    U32,
+    /// little endian u64
+    U64,
+    /// unsigned LEB128 varint
+    Varint,
+}
+
+/// How i16/u16, i32/u32 and i64/u64 are written,
+/// i8/u8, i128/u128 and floats are always fixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntEncoding {
+    /// fixed width, the original layout
+    Fixed,
+    /// LEB128 varint, zigzag for signed integers
+    Varint,
+}
+
+/// Foreign layouts a config is compatible with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Profile {
+    Native,
+    Bincode,
+    DotNet,
+    Java,
+}
+
+/// Encoding options used by the serde `Serializer`/`Deserializer`.
+///
+/// The default config produces the crate's original layout,
+/// the reader must use the same config as the writer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub(crate) skippable_fields: bool,
+    pub(crate) tagged: bool,
+    pub(crate) positional_structs: bool,
+    pub(crate) variant_index: bool,
+    pub(crate) fixed_tuples: bool,
+    pub(crate) profile: Profile,
+    pub(crate) len_encoding: LenEncoding,
+    pub(crate) int_encoding: IntEncoding,
+    pub(crate) human_readable: bool,
+}
+
+impl Default for Config {
+    #[inline]
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
+impl Config {
+    #[inline]
+    pub const fn new() -> Self {
+        Config {
+            skippable_fields: false,
+            tagged: false,
+            positional_structs: false,
+            variant_index: false,
+            fixed_tuples: false,
+            profile: Profile::Native,
+            len_encoding: LenEncoding::U32,
+            int_encoding: IntEncoding::Fixed,
+            human_readable: true,
+        }
+    }
+
+    /// The layout of bincode 1.x's `bincode::serialize`/`bincode::deserialize`:
+    /// little endian u64 lengths, u32 variant indices, no field names, tuple lengths
+    /// or unit bytes. Numbers are little endian whatever the `big_endian` feature.
+    #[inline]
+    pub const fn bincode() -> Self {
+        let mut config = Config::new()
+            .len_encoding(LenEncoding::U64)
+            .positional_structs(true)
+            .variant_index(true)
+            .fixed_tuples(true)
+            .human_readable(false);
+        config.profile = Profile::Bincode;
+        config
+    }
+
+    /// The layout of .NET's `BinaryWriter`/`BinaryReader`: little endian numbers,
+    /// strings and bytes prefixed by a 7 bit encoded length, chars as their utf8 bytes
+    /// and enums as an int. Structs and tuples are their fields in order,
+    /// chars outside the basic multilingual plane are rejected.
+    #[inline]
+    pub const fn dotnet() -> Self {
+        let mut config = Config::bincode().len_encoding(LenEncoding::Varint);
+        config.profile = Profile::DotNet;
+        config
+    }
+
+    /// The layout of Java's `DataOutputStream`/`DataInputStream`: big endian numbers
+    /// whatever the `big_endian` feature, strings as `writeUTF`, chars as `writeChar`,
+    /// bytes, sequences and maps prefixed by their `writeInt` length and enums as an int.
+    /// Structs and tuples are their fields in order.
+    #[inline]
+    pub const fn java() -> Self {
+        let mut config = Config::bincode().len_encoding(LenEncoding::U32);
+        config.profile = Profile::Java;
+        config
+    }
+
+    /// Prefix every struct field value with its u32 byte length.
+    /// Readers then skip fields they don't know instead of failing,
+    /// and missing fields fall back to `#[serde(default)]`.
+    #[inline]
+    pub const fn skippable_fields(mut self, enable: bool) -> Self {
+        self.skippable_fields = enable;
+        self
+    }
+
+    /// Write a one byte type tag before every value, making the output self-describing.
+    /// This enables `deserialize_any`, so `serde_json::Value`, untagged and internally
+    /// tagged enums and `#[serde(flatten)]` round-trip. Tagged values can always be
+    /// skipped, `skippable_fields` has no extra effect in this mode.
+    #[inline]
+    pub const fn tagged(mut self, enable: bool) -> Self {
+        self.tagged = enable;
+        self
+    }
+
+    /// Write struct and struct variant fields in declaration order without their names.
+    /// Fields are read back by position, so `skip_serializing_if` is rejected
+    /// and the reader's struct must have the same fields as the writer's.
+    #[inline]
+    pub const fn positional_structs(mut self, enable: bool) -> Self {
+        self.positional_structs = enable;
+        self
+    }
+
+    /// Write enum variants as their varint index instead of their name.
+    /// Variants are read back by position, so reordering or removing
+    /// variants breaks old data.
+    #[inline]
+    pub const fn variant_index(mut self, enable: bool) -> Self {
+        self.variant_index = enable;
+        self
+    }
+
+    /// Leave out the length of tuples, tuple structs, tuple variants and arrays,
+    /// the reader trusts the length known by its type. Tagged output keeps it.
+    #[inline]
+    pub const fn fixed_tuples(mut self, enable: bool) -> Self {
+        self.fixed_tuples = enable;
+        self
+    }
+
+    /// Encoding of string, bytes, sequence and map lengths.
+    #[inline]
+    pub const fn len_encoding(mut self, encoding: LenEncoding) -> Self {
+        self.len_encoding = encoding;
+        self
+    }
+
+    /// Encoding of 16, 32 and 64 bit integers.
+    #[inline]
+    pub const fn int_encoding(mut self, encoding: IntEncoding) -> Self {
+        self.int_encoding = encoding;
+        self
+    }
+
+    /// The value returned by `is_human_readable`. Types such as `IpAddr`
+    /// serialize as text when true and as compact bytes when false,
+    /// the reader must use the same setting.
+    #[inline]
+    pub const fn human_readable(mut self, enable: bool) -> Self {
+        self.human_readable = enable;
+        self
+    }
+}
+
+impl Config {
+    /// foreign layouts write the variant index as a fixed u32,
+    /// nothing for units and chars as their utf8 bytes
+    #[inline]
+    pub(crate) const fn foreign(&self) -> bool {
+        !matches!(self.profile, Profile::Native)
+    }
+
+    /// bincode and .NET numbers and lengths are little endian whatever the `big_endian` feature
+    #[inline]
+    pub(crate) const fn little_endian(&self) -> bool {
+        matches!(self.profile, Profile::Bincode | Profile::DotNet)
+    }
+
+    /// tuples have a length prefix
+    #[inline]
+    pub(crate) const fn tuple_len(&self) -> bool {
+        self.tagged || !self.fixed_tuples
+    }
+}
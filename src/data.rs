@@ -1,5 +1,8 @@
+use crate::config::Profile;
+use crate::serde::adapter::Adapter;
 use crate::serde::error::DataError;
-use anyhow::{ensure, Result};
+use crate::{Config, LenEncoding};
+use anyhow::{bail, ensure, Result};
 use auto_impl::auto_impl;
 use serde::Serialize;
 use std::mem::size_of;
@@ -9,6 +12,13 @@ use std::ops::{Deref, DerefMut};
 pub trait WriteNumberFixed {
     fn write(&self, data: &mut Data);
     fn write_at(&self, idx: usize, data: &mut Data) -> Result<()>;
+    /// like `write`, but returns an error instead of panicking
+    /// when a length doesn't fit the length prefix
+    #[inline]
+    fn try_write(&self, data: &mut Data) -> Result<()> {
+        self.write(data);
+        Ok(())
+    }
 }
 #[auto_impl(&, &mut,Box)]
 pub trait WriteNumberVar {
@@ -19,6 +29,29 @@ pub trait WriteNumberVar {
 pub struct Data {
     buff: Vec<u8>,
     pub mode: u8,
+    pub(crate) config: Config,
+    /// the field adapter in effect while serializing
+    pub(crate) adapter: Adapter,
+    /// lengths written after their content
+    lens: PendingLens,
+}
+
+/// bytes reserved for a varint length written after its content
+const VAR_LEN_SLOT: usize = 10;
+
+/// Lengths written after their content, see `Data::begin_len`.
+///
+/// A varint length takes a full `VAR_LEN_SLOT` padded varint at first, so the content
+/// never moves while it is written. The padding is removed in a single pass
+/// once no length is open.
+#[derive(Debug, Default)]
+struct PendingLens {
+    /// the content start and `slack` of each open length, innermost last
+    open: Vec<(usize, usize)>,
+    /// the padding of the varint lengths written so far, as (offset, size)
+    gaps: Vec<(usize, usize)>,
+    /// the total size of `gaps`
+    slack: usize,
 }
 
 macro_rules! impl_number_fixed {
@@ -124,64 +157,74 @@ impl WriteNumberFixed for bool {
 impl WriteNumberFixed for String {
     #[inline]
     fn write(&self, data: &mut Data) {
-        let buff = self.as_bytes();
-        data.write_fixed(buff.len() as u32);
-        data.write_buf(buff);
+        data.write_fixed(self.as_bytes())
     }
 
     #[inline]
     fn write_at(&self, idx: usize, data: &mut Data) -> Result<()> {
-        let buff = self.as_bytes();
-        data.write_fixed_at(idx, buff.len() as u32)?;
-        data.write_buf_at(idx + 4, buff)?;
-        Ok(())
+        data.write_fixed_at(idx, self.as_bytes())
+    }
+
+    #[inline]
+    fn try_write(&self, data: &mut Data) -> Result<()> {
+        data.try_write_fixed(self.as_bytes())
     }
 }
 
 impl WriteNumberFixed for &str {
     #[inline]
     fn write(&self, data: &mut Data) {
-        let buff = self.as_bytes();
-        data.write_fixed(buff.len() as u32);
-        data.write_buf(buff);
+        data.write_fixed(self.as_bytes())
     }
 
     #[inline]
     fn write_at(&self, idx: usize, data: &mut Data) -> Result<()> {
-        let buff = self.as_bytes();
-        data.write_fixed_at(idx, buff.len() as u32)?;
-        data.write_buf_at(idx + 4, buff)?;
-        Ok(())
+        data.write_fixed_at(idx, self.as_bytes())
+    }
+
+    #[inline]
+    fn try_write(&self, data: &mut Data) -> Result<()> {
+        data.try_write_fixed(self.as_bytes())
     }
 }
 
-impl<'a> WriteNumberFixed for &'a [u8] {
+impl WriteNumberFixed for &[u8] {
+    /// panics if the length doesn't fit the length prefix, see `try_write`
     #[inline]
     fn write(&self, data: &mut Data) {
-        let len = self.len() as u32;
-        data.write_fixed(len);
-        data.write_buf(self);
+        self.try_write(data)
+            .expect("length too large for the length prefix")
     }
 
     #[inline]
     fn write_at(&self, idx: usize, data: &mut Data) -> Result<()> {
-        let len = self.len() as u32;
-        data.write_fixed_at(idx, len)?;
-        data.write_buf_at(idx + 4, self)?;
+        let size = data.write_len_at(idx, self.len())?;
+        data.write_buf_at(idx + size, self)?;
+        Ok(())
+    }
+
+    #[inline]
+    fn try_write(&self, data: &mut Data) -> Result<()> {
+        data.write_len(self.len())?;
+        data.write_buf(self);
         Ok(())
     }
 }
 
 impl WriteNumberFixed for Vec<u8> {
+    #[inline]
     fn write(&self, data: &mut Data) {
-        data.write_fixed(self.len() as u32);
-        data.write_buf(self);
+        data.write_fixed(self.as_slice())
     }
 
+    #[inline]
     fn write_at(&self, idx: usize, data: &mut Data) -> Result<()> {
-        data.write_fixed_at(idx, self.len() as u32)?;
-        data.write_buf_at(idx + 4, self)?;
-        Ok(())
+        data.write_fixed_at(idx, self.as_slice())
+    }
+
+    #[inline]
+    fn try_write(&self, data: &mut Data) -> Result<()> {
+        data.try_write_fixed(self.as_slice())
     }
 }
 
@@ -277,6 +320,30 @@ fn zig_zag_encode_u64(v: &i64) -> u64 {
     ((v << 1) ^ (v >> 63)) as u64
 }
 
+#[inline]
+fn len_u32(len: usize) -> Result<u32> {
+    ensure!(
+        len <= u32::MAX as usize,
+        "length {} too large for u32 prefix,use LenEncoding::U64 or LenEncoding::Varint",
+        len
+    );
+    Ok(len as u32)
+}
+
+#[inline]
+fn encode_var_len(len: usize) -> ([u8; 10], usize) {
+    let mut buff = [0u8; 10];
+    let mut size = 0;
+    let mut value = len as u64;
+    while value >= 0x80 {
+        buff[size] = (value as u8) | 0x80;
+        value >>= 7;
+        size += 1;
+    }
+    buff[size] = value as u8;
+    (buff, size + 1)
+}
+
 impl WriteNumberVar for String {
     #[inline]
     fn write(&self, data: &mut Data) {
@@ -318,6 +385,9 @@ impl Data {
         Data {
             buff: Vec::with_capacity(4096),
             mode: 0,
+            config: Config::new(),
+            adapter: Adapter::None,
+            lens: PendingLens::default(),
         }
     }
 
@@ -333,6 +403,9 @@ impl Data {
         Data {
             buff: Vec::with_capacity(cap),
             mode: 0,
+            config: Config::new(),
+            adapter: Adapter::None,
+            lens: PendingLens::default(),
         }
     }
 
@@ -343,11 +416,43 @@ impl Data {
         Ok(data)
     }
 
+    #[inline]
+    pub fn serialize_with<T: Serialize>(config: Config, value: T) -> Result<Self, DataError> {
+        let mut data = Data::new();
+        data.config = config;
+        data.serde_serialize(value)?;
+        Ok(data)
+    }
+
     #[inline]
     pub fn into_inner(self) -> Vec<u8> {
         self.buff
     }
 
+    #[inline]
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    #[inline]
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// like `Vec::truncate`, also forgets the lengths written after `len`,
+    /// used to roll back a failed write
+    #[inline]
+    pub fn truncate(&mut self, len: usize) {
+        self.buff.truncate(len);
+        self.drop_stale_lens();
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.buff.clear();
+        self.lens = PendingLens::default();
+    }
+
     #[inline]
     pub fn write_buf(&mut self, buff: &[u8]) {
         unsafe {
@@ -373,11 +478,19 @@ impl Data {
         Ok(())
     }
 
+    /// strings and bytes panic if their length doesn't fit the length prefix,
+    /// `try_write_fixed` returns an error instead
     #[inline]
     pub fn write_fixed(&mut self, v: impl WriteNumberFixed) {
         v.write(self)
     }
 
+    /// like `write_fixed`, fails if a length doesn't fit the length prefix
+    #[inline]
+    pub fn try_write_fixed(&mut self, v: impl WriteNumberFixed) -> Result<()> {
+        v.try_write(self)
+    }
+
     #[inline]
     pub fn write_fixed_at(&mut self, idx: usize, v: impl WriteNumberFixed) -> Result<()> {
         v.write_at(idx, self)
@@ -388,6 +501,252 @@ impl Data {
         v.write(self);
     }
 
+    /// write the low `bytes` bytes of `v`, for 24 or 48 bit integers,
+    /// fails if `v` doesn't fit
+    #[inline]
+    pub fn write_uint_n(&mut self, v: u64, bytes: usize) -> Result<()> {
+        ensure!(
+            (1..=8).contains(&bytes),
+            "uint byte count {} not in 1..=8",
+            bytes
+        );
+        ensure!(
+            bytes == 8 || v >> (bytes * 8) == 0,
+            "{} too large for a {} byte uint",
+            v,
+            bytes
+        );
+        self.write_int_bytes(v, bytes);
+        Ok(())
+    }
+
+    /// write the low `bytes` bytes of `v` in two's complement,
+    /// fails if `v` doesn't fit
+    #[inline]
+    pub fn write_int_n(&mut self, v: i64, bytes: usize) -> Result<()> {
+        ensure!(
+            (1..=8).contains(&bytes),
+            "int byte count {} not in 1..=8",
+            bytes
+        );
+        ensure!(
+            bytes == 8 || (v >> (bytes * 8 - 1)) == 0 || (v >> (bytes * 8 - 1)) == -1,
+            "{} too large for a {} byte int",
+            v,
+            bytes
+        );
+        self.write_int_bytes(v as u64, bytes);
+        Ok(())
+    }
+
+    #[inline]
+    fn write_int_bytes(&mut self, v: u64, bytes: usize) {
+        cfg_if::cfg_if! {
+            if #[cfg(feature ="big_endian")]{
+                self.write_buf(&v.to_be_bytes()[8 - bytes..]);
+            }else{
+                self.write_buf(&v.to_le_bytes()[..bytes]);
+            }
+        }
+    }
+
+    /// write `v` into a `len` byte field filled up with `pad`, like `char name[16]`,
+    /// fails if `v` is longer than `len`
+    #[inline]
+    pub fn write_padded_str(&mut self, v: &str, len: usize, pad: u8) -> Result<()> {
+        ensure!(
+            v.len() <= len,
+            "string length {} too large for a {} byte field",
+            v.len(),
+            len
+        );
+        self.write_buf(v.as_bytes());
+        let end = self.len() + len - v.len();
+        self.buff.resize(end, pad);
+        Ok(())
+    }
+
+    /// write `v` and a NUL, fails if `v` holds a NUL
+    #[inline]
+    pub fn write_cstr(&mut self, v: &str) -> Result<()> {
+        ensure!(!v.as_bytes().contains(&0), "C string {:?} holds a NUL", v);
+        self.write_buf(v.as_bytes());
+        self.write_fixed(0u8);
+        Ok(())
+    }
+
+    /// write a length in the config's `LenEncoding`,
+    /// fails if it doesn't fit `LenEncoding::U32`
+    #[inline]
+    pub(crate) fn write_len(&mut self, len: usize) -> Result<()> {
+        match self.config.len_encoding {
+            LenEncoding::U32 if self.config.profile == Profile::Java => {
+                self.write_java_fixed(len_u32(len)?)
+            }
+            LenEncoding::U32 if self.config.little_endian() => self.write_net_fixed(len_u32(len)?),
+            LenEncoding::U32 => self.write_fixed(len_u32(len)?),
+            LenEncoding::U64 if self.config.little_endian() => self.write_net_fixed(len as u64),
+            LenEncoding::U64 => self.write_fixed(len as u64),
+            LenEncoding::Varint => self.write_var_integer(len as u64),
+        }
+        Ok(())
+    }
+
+    /// overwrite the bytes at `idx` with a length in the config's `LenEncoding`,
+    /// returns the size of the written prefix
+    #[inline]
+    pub(crate) fn write_len_at(&mut self, idx: usize, len: usize) -> Result<usize> {
+        match self.config.len_encoding {
+            LenEncoding::U32 if self.config.profile == Profile::Java => {
+                self.write_buf_at(idx, &len_u32(len)?.to_be_bytes())?;
+                Ok(4)
+            }
+            LenEncoding::U32 if self.config.little_endian() => {
+                self.write_buf_at(idx, &len_u32(len)?.to_le_bytes())?;
+                Ok(4)
+            }
+            LenEncoding::U32 => {
+                self.write_fixed_at(idx, len_u32(len)?)?;
+                Ok(4)
+            }
+            LenEncoding::U64 if self.config.little_endian() => {
+                self.write_buf_at(idx, &(len as u64).to_le_bytes())?;
+                Ok(8)
+            }
+            LenEncoding::U64 => {
+                self.write_fixed_at(idx, len as u64)?;
+                Ok(8)
+            }
+            LenEncoding::Varint => {
+                let (buff, size) = encode_var_len(len);
+                self.write_buf_at(idx, &buff[..size])?;
+                Ok(size)
+            }
+        }
+    }
+
+    /// reserve a length in the config's `LenEncoding`, written later by `patch_len`,
+    /// returns its offset
+    #[inline]
+    pub(crate) fn reserve_len(&mut self) -> Result<usize> {
+        if self.config.len_encoding == LenEncoding::Varint {
+            return Ok(self.reserve_var_len());
+        }
+        let idx = self.len();
+        self.write_len(0)?;
+        Ok(idx)
+    }
+
+    /// write `len` in the length reserved by `reserve_len` at `idx`
+    #[inline]
+    pub(crate) fn patch_len(&mut self, idx: usize, len: usize) -> Result<()> {
+        if self.config.len_encoding == LenEncoding::Varint {
+            return self.patch_var_len(idx, len);
+        }
+        self.write_len_at(idx, len)?;
+        self.compact_lens();
+        Ok(())
+    }
+
+    /// reserve a varint length, written later by `patch_var_len`, returns its offset
+    #[inline]
+    pub(crate) fn reserve_var_len(&mut self) -> usize {
+        let idx = self.len();
+        let mut slot = [0x80u8; VAR_LEN_SLOT];
+        slot[VAR_LEN_SLOT - 1] = 0;
+        self.write_buf(&slot);
+        idx
+    }
+
+    /// write the varint `len` in the slot reserved at `idx`,
+    /// padded to the slot size until no length is open
+    #[inline]
+    pub(crate) fn patch_var_len(&mut self, idx: usize, len: usize) -> Result<()> {
+        ensure!(
+            idx.wrapping_add(VAR_LEN_SLOT) <= self.len(),
+            "idx too max {}>{}",
+            idx.wrapping_add(VAR_LEN_SLOT),
+            self.len()
+        );
+        let (buff, size) = encode_var_len(len);
+        let slot = &mut self.buff[idx..idx + VAR_LEN_SLOT];
+        slot[..size].copy_from_slice(&buff[..size]);
+        if size < VAR_LEN_SLOT {
+            slot[size - 1] |= 0x80;
+            for b in &mut slot[size..VAR_LEN_SLOT - 1] {
+                *b = 0x80;
+            }
+            slot[VAR_LEN_SLOT - 1] = 0;
+            self.lens.gaps.push((idx + size, VAR_LEN_SLOT - size));
+            self.lens.slack += VAR_LEN_SLOT - size;
+        }
+        self.compact_lens();
+        Ok(())
+    }
+
+    /// start the content of a length reserved just before, returns the content start
+    #[inline]
+    pub(crate) fn begin_len(&mut self) -> usize {
+        self.drop_stale_lens();
+        let start = self.len();
+        self.lens.open.push((start, self.lens.slack));
+        start
+    }
+
+    /// end the content started at `start`, returns its size without the varint padding
+    #[inline]
+    pub(crate) fn end_len(&mut self, start: usize) -> Result<usize> {
+        self.drop_stale_lens();
+        // lengths left open by a failed write are abandoned
+        while let Some((open, slack)) = self.lens.open.pop() {
+            if open == start {
+                return Ok(self.len() - start - (self.lens.slack - slack));
+            }
+        }
+        bail!("no length open at {}", start)
+    }
+
+    /// forget the lengths truncated away after a failed write
+    #[inline]
+    fn drop_stale_lens(&mut self) {
+        let len = self.len();
+        while matches!(self.lens.open.last(), Some(&(start, _)) if start > len) {
+            self.lens.open.pop();
+        }
+        while let Some(&(offset, size)) = self.lens.gaps.last() {
+            if offset + size <= len {
+                break;
+            }
+            self.lens.gaps.pop();
+            self.lens.slack -= size;
+        }
+    }
+
+    /// remove the varint padding once no length is open
+    #[inline]
+    pub(crate) fn compact_lens(&mut self) {
+        self.drop_stale_lens();
+        if !self.lens.open.is_empty() || self.lens.gaps.is_empty() {
+            return;
+        }
+        let mut gaps = std::mem::take(&mut self.lens.gaps);
+        gaps.sort_unstable();
+        for &(offset, _) in gaps.iter() {
+            // the last byte of each varint ends it now
+            self.buff[offset - 1] &= 0x7f;
+        }
+        let mut write = gaps[0].0;
+        for (i, &(offset, size)) in gaps.iter().enumerate() {
+            let end = gaps.get(i + 1).map_or(self.buff.len(), |gap| gap.0);
+            self.buff.copy_within(offset + size..end, write);
+            write += end - offset - size;
+        }
+        self.buff.truncate(write);
+        gaps.clear();
+        self.lens.gaps = gaps;
+        self.lens.slack = 0;
+    }
+
     #[inline]
     pub fn check_reserve(&mut self, size: usize) -> usize {
         let len = self.len();
@@ -470,6 +829,12 @@ pub fn compute_raw_varint32_size(value: u32) -> usize {
 
 impl From<Vec<u8>> for Data {
     fn from(buff: Vec<u8>) -> Self {
-        Data { buff, mode: 0 }
+        Data {
+            buff,
+            mode: 0,
+            config: Config::new(),
+            adapter: Adapter::None,
+            lens: PendingLens::default(),
+        }
     }
 }
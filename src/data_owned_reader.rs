@@ -1,4 +1,4 @@
-use crate::{DataReader, ReadNumberFixed, ReadNumberVar};
+use crate::{Config, DataReader, ReadNumberFixed, ReadNumberVar};
 use anyhow::{ensure, Result};
 use std::ops::Deref;
 
@@ -6,12 +6,17 @@ use std::ops::Deref;
 pub struct DataOwnedReader {
     pub(crate) inner: Vec<u8>,
     pub(crate) offset: usize,
+    pub(crate) config: Config,
 }
 
 impl DataOwnedReader {
     #[inline]
     pub fn new(inner: Vec<u8>) -> DataOwnedReader {
-        DataOwnedReader { inner, offset: 0 }
+        DataOwnedReader {
+            inner,
+            offset: 0,
+            config: Config::new(),
+        }
     }
 
     #[inline]
@@ -19,13 +24,23 @@ impl DataOwnedReader {
         self.inner
     }
     #[inline]
-    pub fn get_reader(&self) -> DataReader {
-        DataReader::from(&self.inner[self.offset..])
+    pub fn config(&self) -> Config {
+        self.config
     }
 
     #[inline]
-    pub fn get_all_reader(&self) -> DataReader {
-        DataReader::from(&self.inner[..])
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    #[inline]
+    pub fn get_reader(&self) -> DataReader<'_> {
+        reader_with(&self.inner[self.offset..], self.config)
+    }
+
+    #[inline]
+    pub fn get_all_reader(&self) -> DataReader<'_> {
+        reader_with(&self.inner[..], self.config)
     }
     #[inline]
     pub fn get_offset(&self) -> usize {
@@ -70,7 +85,7 @@ impl DataOwnedReader {
 
     #[inline]
     pub fn read_fixed_buf(&mut self) -> Result<&[u8]> {
-        let mut dr = DataReader::from(&self.inner[self.offset..]);
+        let mut dr = reader_with(&self.inner[self.offset..], self.config);
         let x = dr.read_fixed_buf()?;
         self.offset += dr.offset();
         Ok(x)
@@ -78,7 +93,7 @@ impl DataOwnedReader {
 
     #[inline]
     pub fn read_var_buf(&mut self) -> Result<&[u8]> {
-        let mut dr = DataReader::from(&self.inner[self.offset..]);
+        let mut dr = reader_with(&self.inner[self.offset..], self.config);
         let x = dr.read_var_buf()?;
         self.offset += dr.offset();
         Ok(x)
@@ -86,7 +101,7 @@ impl DataOwnedReader {
 
     #[inline]
     pub fn read_fixed_str(&mut self) -> Result<&str> {
-        let mut dr = DataReader::from(&self.inner[self.offset..]);
+        let mut dr = reader_with(&self.inner[self.offset..], self.config);
         let x = dr.read_fixed_str()?;
         self.offset += dr.offset();
         Ok(x)
@@ -94,21 +109,61 @@ impl DataOwnedReader {
 
     #[inline]
     pub fn read_var_str(&mut self) -> Result<&str> {
-        let mut dr = DataReader::from(&self.inner[self.offset..]);
+        let mut dr = reader_with(&self.inner[self.offset..], self.config);
         let x = dr.read_var_str()?;
         self.offset += dr.offset();
         Ok(x)
     }
 
+    #[inline]
+    pub fn read_uint_n(&mut self, bytes: usize) -> Result<u64> {
+        let mut dr = self.get_reader();
+        let v = dr.read_uint_n(bytes)?;
+        self.offset += dr.offset();
+        Ok(v)
+    }
+
+    #[inline]
+    pub fn read_int_n(&mut self, bytes: usize) -> Result<i64> {
+        let mut dr = self.get_reader();
+        let v = dr.read_int_n(bytes)?;
+        self.offset += dr.offset();
+        Ok(v)
+    }
+
+    #[inline]
+    pub fn read_padded_str(&mut self, len: usize) -> Result<&str> {
+        let mut dr = reader_with(&self.inner[self.offset..], self.config);
+        let x = dr.read_padded_str(len)?;
+        self.offset += dr.offset();
+        Ok(x)
+    }
+
+    #[inline]
+    pub fn read_cstr(&mut self) -> Result<&str> {
+        let mut dr = reader_with(&self.inner[self.offset..], self.config);
+        let x = dr.read_cstr()?;
+        self.offset += dr.offset();
+        Ok(x)
+    }
+
     #[inline]
     pub fn read_buff(&mut self, buff: &mut [u8]) -> Result<()> {
-        let mut dr = DataReader::from(&self.inner[self.offset..]);
+        let mut dr = self.get_reader();
         dr.read_buff(buff)?;
         self.offset += dr.offset();
         Ok(())
     }
 }
 
+/// a reader borrowing only the buffer, so the offset can move while its result is held
+#[inline]
+fn reader_with(buff: &[u8], config: Config) -> DataReader<'_> {
+    let mut dr = DataReader::from(buff);
+    dr.config = config;
+    dr
+}
+
 impl Deref for DataOwnedReader {
     type Target = [u8];
 
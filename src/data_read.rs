@@ -1,4 +1,7 @@
+use crate::config::Profile;
+use crate::serde::adapter::Adapter;
 use crate::serde::error::DataError;
+use crate::{Config, LenEncoding};
 use anyhow::{bail, ensure, Result};
 use serde::Deserialize;
 use std::convert::TryInto;
@@ -225,6 +228,9 @@ pub struct DataReader<'a> {
     pub(crate) buff: &'a [u8],
     pub(crate) original_len: usize,
     pub(crate) mode: u8,
+    pub(crate) config: Config,
+    /// the field adapter in effect while deserializing
+    pub(crate) adapter: Adapter,
 }
 
 impl<'a> From<&'a [u8]> for DataReader<'a> {
@@ -234,6 +240,8 @@ impl<'a> From<&'a [u8]> for DataReader<'a> {
             buff,
             original_len: buff.len(),
             mode: 0,
+            config: Config::new(),
+            adapter: Adapter::None,
         }
     }
 }
@@ -261,6 +269,8 @@ impl<'a> DataReader<'a> {
             original_len: buff.len(),
             buff,
             mode: 0,
+            config: Config::new(),
+            adapter: Adapter::None,
         }
     }
 
@@ -273,9 +283,36 @@ impl<'a> DataReader<'a> {
             original_len: buff.len(),
             buff,
             mode: 0,
+            config: Config::new(),
+            adapter: Adapter::None,
         })
     }
 
+    #[inline]
+    pub fn deserialize_with<'de, D: Deserialize<'de>, T: AsRef<[u8]>>(
+        config: Config,
+        v: &'de T,
+    ) -> Result<D, DataError> {
+        let buff = v.as_ref();
+        D::deserialize(&mut DataReader {
+            original_len: buff.len(),
+            buff,
+            mode: 0,
+            config,
+            adapter: Adapter::None,
+        })
+    }
+
+    #[inline]
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    #[inline]
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
     #[inline]
     pub fn advance(&mut self, cnt: usize) -> Result<()> {
         ensure!(
@@ -317,38 +354,53 @@ impl<'a> DataReader<'a> {
     #[inline]
     pub fn read_var_str(&mut self) -> Result<&'a str> {
         let len = self.read_var_integer::<u64>()? as usize;
-        ensure!(
-            len <= self.len(),
-            "read string size too big,{}>{}",
-            len,
-            self.len()
-        );
-        let (res, have) = self.buff.split_at(len);
-        self.buff = have;
+        self.take_str(len)
+    }
 
-        cfg_if::cfg_if! {
-            if #[cfg(feature ="check_utf8")]{
-                 Ok(std::str::from_utf8(res)?)
-            }else{
-               unsafe {
-                    Ok(std::str::from_utf8_unchecked(res))
-               }
+    #[inline]
+    pub fn read_fixed_str(&mut self) -> Result<&'a str> {
+        let len = self.read_len()?;
+        self.take_str(len)
+    }
+
+    #[inline]
+    pub fn read_var_buf(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_var_integer::<u64>()? as usize;
+        self.take_buf(len)
+    }
+
+    #[inline]
+    pub fn read_fixed_buf(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_len()?;
+        self.take_buf(len)
+    }
+
+    /// read a length written in the config's `LenEncoding`
+    #[inline]
+    pub(crate) fn read_len(&mut self) -> Result<usize> {
+        let len = match self.config.len_encoding {
+            LenEncoding::U32 if self.config.profile == Profile::Java => {
+                return Ok(self.read_java_fixed::<u32>()? as usize)
             }
-        }
+            LenEncoding::U32 if self.config.little_endian() => {
+                return Ok(self.read_net_fixed::<u32>()? as usize)
+            }
+            LenEncoding::U32 => return Ok(self.read_fixed::<u32>()? as usize),
+            LenEncoding::U64 if self.config.little_endian() => self.read_net_fixed::<u64>()?,
+            LenEncoding::U64 => self.read_fixed::<u64>()?,
+            LenEncoding::Varint => self.read_var_integer::<u64>()?,
+        };
+        Ok(len.try_into()?)
     }
 
     #[inline]
-    pub fn read_fixed_str(&mut self) -> Result<&'a str> {
-        let len = self.read_fixed::<u32>()? as usize;
-        ensure!(
-            len <= self.len(),
-            "read string size too big,{}>{}",
-            len,
-            self.len()
-        );
-        let (res, have) = self.buff.split_at(len);
-        self.buff = have;
+    pub(crate) fn take_str(&mut self, len: usize) -> Result<&'a str> {
+        let res = self.take_buf(len)?;
+        Self::to_str(res)
+    }
 
+    #[inline]
+    fn to_str(res: &'a [u8]) -> Result<&'a str> {
         cfg_if::cfg_if! {
             if #[cfg(feature ="check_utf8")]{
                  Ok(std::str::from_utf8(res)?)
@@ -361,8 +413,7 @@ impl<'a> DataReader<'a> {
     }
 
     #[inline]
-    pub fn read_var_buf(&mut self) -> Result<&'a [u8]> {
-        let len = self.read_var_integer::<u64>()? as usize;
+    pub(crate) fn take_buf(&mut self, len: usize) -> Result<&'a [u8]> {
         ensure!(
             len <= self.len(),
             "read string size too big,{}>{}",
@@ -375,26 +426,61 @@ impl<'a> DataReader<'a> {
     }
 
     #[inline]
-    pub fn read_fixed_buf(&mut self) -> Result<&'a [u8]> {
-        let len = self.read_fixed::<u32>()? as usize;
+    pub fn read_fixed<T: ReadNumberFixed>(&mut self) -> Result<T> {
+        T::read(self)
+    }
+
+    #[inline]
+    pub fn read_var_integer<T: ReadNumberVar>(&mut self) -> Result<T> {
+        T::read(self)
+    }
+
+    /// read a `bytes` byte unsigned integer, for 24 or 48 bit integers
+    #[inline]
+    pub fn read_uint_n(&mut self, bytes: usize) -> Result<u64> {
         ensure!(
-            len <= self.len(),
-            "read string size too big,{}>{}",
-            len,
-            self.len()
+            (1..=8).contains(&bytes),
+            "uint byte count {} not in 1..=8",
+            bytes
         );
-        let (res, have) = self.buff.split_at(len);
-        self.buff = have;
-        Ok(res)
+        let res = self.take_buf(bytes)?;
+        let mut buff = [0; 8];
+        cfg_if::cfg_if! {
+            if #[cfg(feature ="big_endian")]{
+                buff[8 - bytes..].copy_from_slice(res);
+                Ok(u64::from_be_bytes(buff))
+            }else{
+                buff[..bytes].copy_from_slice(res);
+                Ok(u64::from_le_bytes(buff))
+            }
+        }
     }
 
+    /// read a `bytes` byte signed integer
     #[inline]
-    pub fn read_fixed<T: ReadNumberFixed>(&mut self) -> Result<T> {
-        T::read(self)
+    pub fn read_int_n(&mut self, bytes: usize) -> Result<i64> {
+        let v = self.read_uint_n(bytes)?;
+        let shift = 64 - bytes * 8;
+        Ok(((v << shift) as i64) >> shift)
     }
 
+    /// read a `len` byte string field, the string ends at its first NUL.
+    /// Other pad bytes are kept, trim them with `trim_end_matches`
     #[inline]
-    pub fn read_var_integer<T: ReadNumberVar>(&mut self) -> Result<T> {
-        T::read(self)
+    pub fn read_padded_str(&mut self, len: usize) -> Result<&'a str> {
+        let res = self.take_buf(len)?;
+        let end = res.iter().position(|&b| b == 0).unwrap_or(len);
+        Self::to_str(&res[..end])
+    }
+
+    /// read a NUL terminated string, the NUL is consumed but not returned
+    #[inline]
+    pub fn read_cstr(&mut self) -> Result<&'a str> {
+        let len = match self.buff.iter().position(|&b| b == 0) {
+            Some(len) => len,
+            None => bail!("C string without NUL"),
+        };
+        let res = self.take_buf(len + 1)?;
+        Self::to_str(&res[..len])
     }
 }
@@ -0,0 +1,382 @@
+//! Integer lists written as varint deltas, for sorted ids, posting lists and timestamps.
+//!
+//! Layout: the value count as a varint, a flags byte, then the values, the first
+//! as a full varint and each next one as its delta to the previous. Sorted values
+//! use unsigned deltas, others zigzag deltas.
+//!
+//! The block layout adds a full varint every `restart` values, so a reader can jump
+//! into the list: after the flags come the restart interval and the body length as
+//! varints, then the u32 offset of each block in the body and the body.
+//!
+//! `#[serde(with = "data_rw::delta_var")]` writes a `Vec<u64>` field as the bytes
+//! of a list without blocks.
+
+use crate::{Data, DataReader};
+use anyhow::{anyhow, bail, ensure, Result};
+use serde::de::{Error, SeqAccess, Visitor};
+use serde::{Deserializer, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// the deltas are zigzag, the values aren't sorted
+const ZIGZAG: u8 = 1;
+/// the list is in blocks with restart points
+const BLOCKS: u8 = 2;
+
+#[inline]
+fn is_sorted(values: &[u64]) -> bool {
+    values.windows(2).all(|w| w[0] <= w[1])
+}
+
+impl Data {
+    /// write the values of a block, the first as a full varint
+    #[inline]
+    fn write_deltas(&mut self, values: &[u64], zigzag: bool) {
+        let mut prev = 0u64;
+        for (i, &v) in values.iter().enumerate() {
+            if i == 0 {
+                self.write_var_integer(v);
+            } else if zigzag {
+                self.write_var_integer(v.wrapping_sub(prev) as i64);
+            } else {
+                self.write_var_integer(v - prev);
+            }
+            prev = v;
+        }
+    }
+
+    /// write `values` as varint deltas
+    #[inline]
+    pub fn write_delta_var_slice(&mut self, values: &[u64]) {
+        let zigzag = !is_sorted(values);
+        self.write_var_integer(values.len() as u64);
+        self.write_fixed(if zigzag { ZIGZAG } else { 0 });
+        self.write_deltas(values, zigzag);
+    }
+
+    /// write `values` as varint deltas in blocks of `restart` values,
+    /// see `DataReader::read_delta_var_blocks`
+    #[inline]
+    pub fn write_delta_var_blocks(&mut self, values: &[u64], restart: usize) -> Result<()> {
+        ensure!(restart > 0, "delta var restart interval is 0");
+        let zigzag = !is_sorted(values);
+        let mut body = Data::new();
+        let mut offsets = Vec::with_capacity(values.len().div_ceil(restart));
+        for block in values.chunks(restart) {
+            offsets.push(
+                u32::try_from(body.len()).map_err(|_| anyhow!("delta var body larger than 4GB"))?,
+            );
+            body.write_deltas(block, zigzag);
+        }
+        self.write_var_integer(values.len() as u64);
+        self.write_fixed(if zigzag { ZIGZAG | BLOCKS } else { BLOCKS });
+        self.write_var_integer(restart as u64);
+        self.write_var_integer(body.len() as u64);
+        for offset in offsets {
+            self.write_fixed(offset);
+        }
+        self.write_buf(&body);
+        Ok(())
+    }
+}
+
+impl<'a> DataReader<'a> {
+    /// read a list written by `write_delta_var_slice` or `write_delta_var_blocks`
+    #[inline]
+    pub fn read_delta_var_slice(&mut self) -> Result<Vec<u64>> {
+        let len = self.read_var_integer::<u64>()?;
+        let flags = self.read_fixed::<u8>()?;
+        ensure!(
+            flags & !(ZIGZAG | BLOCKS) == 0,
+            "unknown delta var flags {}",
+            flags
+        );
+        let mut values = Vec::with_capacity((len as usize).min(self.len()));
+        if flags & BLOCKS == BLOCKS {
+            let blocks = self.read_blocks(len, flags)?;
+            for v in blocks.iter() {
+                values.push(v?);
+            }
+        } else {
+            let mut iter = DeltaVarIter {
+                reader: DataReader::from(self.buff),
+                left: len,
+                zigzag: flags & ZIGZAG == ZIGZAG,
+                restart: 0,
+                index: 0,
+                prev: 0,
+            };
+            for v in &mut iter {
+                values.push(v?);
+            }
+            self.buff = iter.reader.buff;
+        }
+        Ok(values)
+    }
+
+    /// read a list written by `write_delta_var_blocks`, its values are decoded on access
+    #[inline]
+    pub fn read_delta_var_blocks(&mut self) -> Result<DeltaVarBlocks<'a>> {
+        let len = self.read_var_integer::<u64>()?;
+        let flags = self.read_fixed::<u8>()?;
+        ensure!(
+            flags & !(ZIGZAG | BLOCKS) == 0,
+            "unknown delta var flags {}",
+            flags
+        );
+        ensure!(flags & BLOCKS == BLOCKS, "delta var list has no blocks");
+        self.read_blocks(len, flags)
+    }
+
+    #[inline]
+    fn read_blocks(&mut self, len: u64, flags: u8) -> Result<DeltaVarBlocks<'a>> {
+        let restart = self.read_var_integer::<u64>()?;
+        ensure!(restart > 0, "delta var restart interval is 0");
+        let body_len = self.read_var_integer::<u64>()? as usize;
+        let blocks = len.div_ceil(restart);
+        let offsets_len = usize::try_from(blocks)
+            .ok()
+            .and_then(|blocks| blocks.checked_mul(4))
+            .ok_or_else(|| anyhow!("delta var block count {} too large", blocks))?;
+        let offsets = self.take_buf(offsets_len)?;
+        let body = self.take_buf(body_len)?;
+        Ok(DeltaVarBlocks {
+            len,
+            zigzag: flags & ZIGZAG == ZIGZAG,
+            restart,
+            offsets,
+            body,
+        })
+    }
+}
+
+/// A list in blocks, read by `DataReader::read_delta_var_blocks`.
+#[derive(Debug, Clone, Copy)]
+pub struct DeltaVarBlocks<'a> {
+    len: u64,
+    zigzag: bool,
+    restart: u64,
+    offsets: &'a [u8],
+    body: &'a [u8],
+}
+
+impl<'a> DeltaVarBlocks<'a> {
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// the values from the start of `block`
+    #[inline]
+    fn block(&self, block: u64) -> Result<DeltaVarIter<'a>> {
+        let at = block as usize * 4;
+        let offset = DataReader::from(&self.offsets[at..at + 4]).read_fixed::<u32>()? as usize;
+        ensure!(
+            offset <= self.body.len(),
+            "delta var block offset {} > {}",
+            offset,
+            self.body.len()
+        );
+        let index = block * self.restart;
+        Ok(DeltaVarIter {
+            reader: DataReader::from(&self.body[offset..]),
+            left: self.len - index,
+            zigzag: self.zigzag,
+            restart: self.restart,
+            index,
+            prev: 0,
+        })
+    }
+
+    /// the values from `index` on, decoding from the block start before it
+    #[inline]
+    pub fn iter_from(&self, index: u64) -> Result<DeltaVarIter<'a>> {
+        ensure!(
+            index <= self.len,
+            "delta var index {} > {}",
+            index,
+            self.len
+        );
+        if index == self.len {
+            return Ok(DeltaVarIter {
+                reader: DataReader::from(&[][..]),
+                left: 0,
+                zigzag: self.zigzag,
+                restart: self.restart,
+                index,
+                prev: 0,
+            });
+        }
+        let mut iter = self.block(index / self.restart)?;
+        for _ in 0..index % self.restart {
+            iter.next()
+                .unwrap_or_else(|| bail!("delta var list ended"))?;
+        }
+        Ok(iter)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> DeltaVarIter<'a> {
+        DeltaVarIter {
+            reader: DataReader::from(self.body),
+            left: self.len,
+            zigzag: self.zigzag,
+            restart: self.restart,
+            index: 0,
+            prev: 0,
+        }
+    }
+
+    /// the value at `index`
+    #[inline]
+    pub fn get(&self, index: u64) -> Result<u64> {
+        ensure!(
+            index < self.len,
+            "delta var index {} >= {}",
+            index,
+            self.len
+        );
+        self.iter_from(index)?
+            .next()
+            .unwrap_or_else(|| bail!("delta var list ended"))
+    }
+
+    /// the index of the first value not less than `value`, or `len()`,
+    /// fails if the list isn't sorted
+    #[inline]
+    pub fn lower_bound(&self, value: u64) -> Result<u64> {
+        ensure!(!self.zigzag, "delta var list isn't sorted");
+        let blocks = self.len.div_ceil(self.restart);
+        // the first block starting at or above `value`
+        let (mut lo, mut hi) = (0, blocks);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let first = self
+                .block(mid)?
+                .next()
+                .unwrap_or_else(|| bail!("delta var list ended"))?;
+            if first < value {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        if lo == 0 {
+            return Ok(0);
+        }
+        let mut index = (lo - 1) * self.restart;
+        for v in self.block(lo - 1)?.take(self.restart as usize) {
+            if v? >= value {
+                break;
+            }
+            index += 1;
+        }
+        Ok(index)
+    }
+}
+
+/// Decodes the values of a delta var list, stops after the first error.
+pub struct DeltaVarIter<'a> {
+    reader: DataReader<'a>,
+    left: u64,
+    zigzag: bool,
+    /// block size, 0 without blocks
+    restart: u64,
+    index: u64,
+    prev: u64,
+}
+
+impl<'a> DeltaVarIter<'a> {
+    #[inline]
+    fn read_next(&mut self) -> Result<u64> {
+        let full = self.index == 0 || (self.restart > 0 && self.index.is_multiple_of(self.restart));
+        let v = if full {
+            self.reader.read_var_integer::<u64>()?
+        } else if self.zigzag {
+            self.prev
+                .wrapping_add(self.reader.read_var_integer::<i64>()? as u64)
+        } else {
+            let delta = self.reader.read_var_integer::<u64>()?;
+            self.prev
+                .checked_add(delta)
+                .ok_or_else(|| anyhow!("delta var value overflow"))?
+        };
+        self.prev = v;
+        self.index += 1;
+        Ok(v)
+    }
+}
+
+impl<'a> Iterator for DeltaVarIter<'a> {
+    type Item = Result<u64>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.left == 0 {
+            return None;
+        }
+        let v = self.read_next();
+        self.left = if v.is_err() { 0 } else { self.left - 1 };
+        Some(v)
+    }
+}
+
+/// Write a `Vec<u64>` as delta var bytes, for `#[serde(with = "data_rw::delta_var")]`.
+#[inline]
+pub fn serialize<S>(values: &[u64], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut data = Data::new();
+    data.write_delta_var_slice(values);
+    serializer.serialize_bytes(&data)
+}
+
+#[inline]
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_bytes(DeltaVarVisitor)
+}
+
+struct DeltaVarVisitor;
+
+impl<'de> Visitor<'de> for DeltaVarVisitor {
+    type Value = Vec<u64>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("delta var bytes")
+    }
+
+    #[inline]
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        let mut rd = DataReader::from(v);
+        let values = rd.read_delta_var_slice().map_err(E::custom)?;
+        if !rd.is_empty() {
+            return Err(E::custom("delta var bytes left over"));
+        }
+        Ok(values)
+    }
+
+    /// human readable formats may write bytes as a sequence
+    #[inline]
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(b) = seq.next_element::<u8>()? {
+            bytes.push(b);
+        }
+        self.visit_bytes(&bytes)
+    }
+}
@@ -0,0 +1,290 @@
+//! Reading and writing the layout of .NET's `System.IO.BinaryWriter`/`BinaryReader`.
+//!
+//! Numbers are little endian whatever the `big_endian` feature,
+//! this module adds the .NET specific encodings. Use `Config::dotnet()` for serde.
+
+use crate::{Data, DataReader};
+use anyhow::{bail, ensure, Result};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// `DateTime.UnixEpoch.Ticks`
+const UNIX_EPOCH_TICKS: u64 = 621_355_968_000_000_000;
+/// `DateTime.MaxValue.Ticks`
+const MAX_TICKS: u64 = 3_155_378_975_999_999_999;
+const NANOS_PER_TICK: u128 = 100;
+
+/// a number written by `BinaryWriter` in little endian
+pub trait NetNumber: Sized {
+    fn write_net(&self, data: &mut Data);
+    fn read_net(dr: &mut DataReader) -> Result<Self>;
+}
+
+macro_rules! impl_net_number {
+    ($($type:ty)+) => {
+        $(
+        impl NetNumber for $type {
+            #[inline]
+            fn write_net(&self, data: &mut Data) {
+                data.write_buf(&self.to_le_bytes());
+            }
+
+            #[inline]
+            fn read_net(dr: &mut DataReader) -> Result<Self> {
+                let mut buff = [0; std::mem::size_of::<$type>()];
+                dr.read_buff(&mut buff)?;
+                Ok(<$type>::from_le_bytes(buff))
+            }
+        }
+        )*
+    };
+}
+
+impl_net_number!(i8 u8 i16 u16 i32 u32 i64 u64 i128 u128 f32 f64);
+
+impl NetNumber for bool {
+    #[inline]
+    fn write_net(&self, data: &mut Data) {
+        data.write_fixed(*self);
+    }
+
+    #[inline]
+    fn read_net(dr: &mut DataReader) -> Result<Self> {
+        dr.read_fixed()
+    }
+}
+
+impl Data {
+    /// `Write(int)`, `Write(long)`, `Write(double)`.. in little endian
+    #[inline]
+    pub fn write_net_fixed(&mut self, v: impl NetNumber) {
+        v.write_net(self)
+    }
+
+    /// `BinaryWriter.Write7BitEncodedInt`, negative values take 5 bytes
+    #[inline]
+    pub fn write_net_7bit_int(&mut self, v: i32) {
+        self.write_var_integer(v as u32);
+    }
+
+    /// `BinaryWriter.Write7BitEncodedInt64`, negative values take 10 bytes
+    #[inline]
+    pub fn write_net_7bit_int64(&mut self, v: i64) {
+        self.write_var_integer(v as u64);
+    }
+
+    /// `BinaryWriter.Write(string)`, the utf8 bytes prefixed by their 7 bit encoded length
+    #[inline]
+    pub fn write_net_string(&mut self, v: &str) -> Result<()> {
+        ensure!(
+            v.len() <= i32::MAX as usize,
+            "string length {} too large for .NET",
+            v.len()
+        );
+        self.write_net_7bit_int(v.len() as i32);
+        self.write_buf(v.as_bytes());
+        Ok(())
+    }
+
+    /// `BinaryWriter.Write(char)`, a .NET char is one utf16 unit,
+    /// so chars outside the basic multilingual plane are rejected
+    #[inline]
+    pub fn write_net_char(&mut self, v: char) -> Result<()> {
+        ensure!(
+            (v as u32) <= 0xffff,
+            "char {:?} is not a single .NET char",
+            v
+        );
+        let mut buf = [0; 4];
+        self.write_buf(v.encode_utf8(&mut buf).as_bytes());
+        Ok(())
+    }
+
+    /// `BinaryWriter.Write(decimal)`, the value is `mantissa / 10^scale`,
+    /// the mantissa has 96 bits and the scale is at most 28
+    #[inline]
+    pub fn write_net_decimal(&mut self, mantissa: i128, scale: u32) -> Result<()> {
+        let abs = mantissa.unsigned_abs();
+        ensure!(
+            abs >> 96 == 0,
+            "decimal mantissa {} exceeds 96 bits",
+            mantissa
+        );
+        ensure!(scale <= 28, "decimal scale {} > 28", scale);
+        let mut flags = scale << 16;
+        if mantissa < 0 {
+            flags |= 0x8000_0000;
+        }
+        self.write_net_fixed(abs as u32);
+        self.write_net_fixed((abs >> 32) as u32);
+        self.write_net_fixed((abs >> 64) as u32);
+        self.write_net_fixed(flags);
+        Ok(())
+    }
+
+    /// `BinaryWriter.Write(DateTime.Ticks)`, 100ns intervals since 0001-01-01 UTC
+    #[inline]
+    pub fn write_net_datetime(&mut self, time: SystemTime) -> Result<()> {
+        self.write_net_fixed(to_net_ticks(time)?);
+        Ok(())
+    }
+}
+
+impl<'a> DataReader<'a> {
+    /// `ReadInt32`, `ReadInt64`, `ReadDouble`.. in little endian
+    #[inline]
+    pub fn read_net_fixed<T: NetNumber>(&mut self) -> Result<T> {
+        T::read_net(self)
+    }
+
+    /// `BinaryReader.Read7BitEncodedInt`
+    #[inline]
+    pub fn read_net_7bit_int(&mut self) -> Result<i32> {
+        Ok(self.read_net_7bit(5)? as u32 as i32)
+    }
+
+    /// `BinaryReader.Read7BitEncodedInt64`
+    #[inline]
+    pub fn read_net_7bit_int64(&mut self) -> Result<i64> {
+        Ok(self.read_net_7bit(10)? as i64)
+    }
+
+    /// read at most `max` bytes, the bits of the last one must fit 32 or 64 bits
+    #[inline]
+    fn read_net_7bit(&mut self, max: u32) -> Result<u64> {
+        let mut v = 0u64;
+        for i in 0..max {
+            let b = self.read_fixed::<u8>()?;
+            if i == max - 1 {
+                let bits = if max == 5 { 32 } else { 64 };
+                ensure!(
+                    (b as u64) >> (bits - 7 * i) == 0,
+                    "bad 7 bit encoded int, last byte:{}",
+                    b
+                );
+            }
+            v |= ((b & 0x7f) as u64) << (7 * i);
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        unreachable!()
+    }
+
+    /// `BinaryReader.ReadString`
+    #[inline]
+    pub fn read_net_string(&mut self) -> Result<&'a str> {
+        let len = self.read_net_7bit_int()?;
+        ensure!(len >= 0, "bad .NET string length {}", len);
+        ensure!(
+            len as usize <= self.len(),
+            "read string size too big,{}>{}",
+            len,
+            self.len()
+        );
+        let (res, have) = self.buff.split_at(len as usize);
+        self.buff = have;
+        Ok(std::str::from_utf8(res)?)
+    }
+
+    /// `BinaryReader.ReadChar`
+    #[inline]
+    pub fn read_net_char(&mut self) -> Result<char> {
+        let v = self.read_utf8_char()?;
+        ensure!(
+            (v as u32) <= 0xffff,
+            "char {:?} is not a single .NET char",
+            v
+        );
+        Ok(v)
+    }
+
+    /// `BinaryReader.ReadDecimal`, returns the mantissa and the scale
+    #[inline]
+    pub fn read_net_decimal(&mut self) -> Result<(i128, u32)> {
+        let lo = self.read_net_fixed::<u32>()? as i128;
+        let mid = self.read_net_fixed::<u32>()? as i128;
+        let hi = self.read_net_fixed::<u32>()? as i128;
+        let flags = self.read_net_fixed::<u32>()?;
+        let scale = (flags >> 16) & 0xff;
+        ensure!(
+            flags & 0x7f00_ffff == 0 && scale <= 28,
+            "bad decimal flags:{:#x}",
+            flags
+        );
+        let mantissa = lo | (mid << 32) | (hi << 64);
+        if flags & 0x8000_0000 != 0 {
+            Ok((-mantissa, scale))
+        } else {
+            Ok((mantissa, scale))
+        }
+    }
+
+    /// `new DateTime(BinaryReader.ReadInt64())`
+    #[inline]
+    pub fn read_net_datetime(&mut self) -> Result<SystemTime> {
+        from_net_ticks(self.read_net_fixed()?)
+    }
+}
+
+/// convert a time to `DateTime.Ticks`
+#[inline]
+pub fn to_net_ticks(time: SystemTime) -> Result<i64> {
+    let ticks = match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => UNIX_EPOCH_TICKS as i128 + (after.as_nanos() / NANOS_PER_TICK) as i128,
+        Err(err) => {
+            let before = err.duration().as_nanos();
+            UNIX_EPOCH_TICKS as i128 - before.div_ceil(NANOS_PER_TICK) as i128
+        }
+    };
+    ensure!(
+        (0..=MAX_TICKS as i128).contains(&ticks),
+        "time out of the .NET DateTime range"
+    );
+    Ok(ticks as i64)
+}
+
+/// convert `DateTime.Ticks` to a time
+#[inline]
+pub fn from_net_ticks(ticks: i64) -> Result<SystemTime> {
+    ensure!(
+        (0..=MAX_TICKS as i64).contains(&ticks),
+        "ticks {} out of the .NET DateTime range",
+        ticks
+    );
+    let ticks = ticks as u64;
+    let time = if ticks >= UNIX_EPOCH_TICKS {
+        UNIX_EPOCH.checked_add(ticks_duration(ticks - UNIX_EPOCH_TICKS))
+    } else {
+        UNIX_EPOCH.checked_sub(ticks_duration(UNIX_EPOCH_TICKS - ticks))
+    };
+    match time {
+        Some(time) => Ok(time),
+        None => bail!("ticks {} out of the SystemTime range", ticks),
+    }
+}
+
+#[inline]
+fn ticks_duration(ticks: u64) -> Duration {
+    Duration::new(ticks / 10_000_000, (ticks % 10_000_000) as u32 * 100)
+}
+
+/// `#[serde(with = "data_rw::dotnet::net_ticks")]` for a `SystemTime` field,
+/// written as the i64 `DateTime.Ticks`
+pub mod net_ticks {
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    #[inline]
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        let ticks = super::to_net_ticks(*time).map_err(S::Error::custom)?;
+        serializer.serialize_i64(ticks)
+    }
+
+    #[inline]
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let ticks = i64::deserialize(deserializer)?;
+        super::from_net_ticks(ticks).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,227 @@
+//! Compressed time series, Gorilla XOR floats and delta-of-delta timestamps.
+//!
+//! Both write the value count as a varint then a bit stream padded to a whole byte.
+//! A float is XORed with the previous one, an unchanged value takes 1 bit and a
+//! change only its meaningful bits. A timestamp is written as the change of its
+//! delta, 1 bit for a regular interval and up to 68 bits for a jump.
+//! The readers return iterators decoding one value per step, the reader is past
+//! the series once the iterator is exhausted.
+
+use crate::bits::{BitReader, BitWriter};
+use crate::{Data, DataReader};
+use anyhow::{bail, ensure, Result};
+
+/// bit widths of the delta-of-delta buckets, bucket `i` is prefixed
+/// by `i + 1` one bits then a zero bit, except for the last
+const DOD_BITS: [u32; 4] = [7, 9, 12, 64];
+
+impl Data {
+    /// write a float series with Gorilla XOR compression
+    #[inline]
+    pub fn write_gorilla(&mut self, values: &[f64]) -> Result<()> {
+        self.write_var_integer(values.len() as u64);
+        let mut bits = BitWriter::new(self);
+        let mut prev = 0u64;
+        // leading zeros and length of the last meaningful bits
+        let mut window: Option<(u32, u32)> = None;
+        for (i, v) in values.iter().enumerate() {
+            let v = v.to_bits();
+            if i == 0 {
+                bits.write_bits(v, 64)?;
+                prev = v;
+                continue;
+            }
+            let xor = v ^ prev;
+            prev = v;
+            if xor == 0 {
+                bits.write_bool(false)?;
+                continue;
+            }
+            bits.write_bool(true)?;
+            let leading = xor.leading_zeros().min(31);
+            let trailing = xor.trailing_zeros();
+            match window {
+                Some((lead, len)) if leading >= lead && trailing >= 64 - lead - len => {
+                    bits.write_bool(false)?;
+                    bits.write_bits(xor >> (64 - lead - len), len)?;
+                }
+                _ => {
+                    let len = 64 - leading - trailing;
+                    bits.write_bool(true)?;
+                    bits.write_bits(leading as u64, 5)?;
+                    bits.write_bits(len as u64 % 64, 6)?;
+                    bits.write_bits(xor >> trailing, len)?;
+                    window = Some((leading, len));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// write a timestamp series with delta-of-delta compression
+    #[inline]
+    pub fn write_delta_of_delta(&mut self, values: &[i64]) -> Result<()> {
+        self.write_var_integer(values.len() as u64);
+        let mut bits = BitWriter::new(self);
+        let mut prev = 0i64;
+        let mut prev_delta = 0i64;
+        for (i, &v) in values.iter().enumerate() {
+            if i == 0 {
+                bits.write_signed(v, 64)?;
+                prev = v;
+                continue;
+            }
+            let delta = v.wrapping_sub(prev);
+            let dod = delta.wrapping_sub(prev_delta);
+            prev = v;
+            prev_delta = delta;
+            if dod == 0 {
+                bits.write_bool(false)?;
+                continue;
+            }
+            for (bucket, &width) in DOD_BITS.iter().enumerate() {
+                let last = bucket == DOD_BITS.len() - 1;
+                if last || (dod >> (width - 1)) == 0 || (dod >> (width - 1)) == -1 {
+                    for _ in 0..=bucket {
+                        bits.write_bool(true)?;
+                    }
+                    if !last {
+                        bits.write_bool(false)?;
+                    }
+                    bits.write_signed(dod, width)?;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> DataReader<'a> {
+    /// read a float series written by `write_gorilla`
+    #[inline]
+    pub fn read_gorilla<'r>(&'r mut self) -> Result<GorillaIter<'r, 'a>> {
+        let left = self.read_var_integer::<u64>()?;
+        Ok(GorillaIter {
+            bits: BitReader::new(self),
+            left,
+            first: true,
+            prev: 0,
+            window: None,
+        })
+    }
+
+    /// read a timestamp series written by `write_delta_of_delta`
+    #[inline]
+    pub fn read_delta_of_delta<'r>(&'r mut self) -> Result<DeltaOfDeltaIter<'r, 'a>> {
+        let left = self.read_var_integer::<u64>()?;
+        Ok(DeltaOfDeltaIter {
+            bits: BitReader::new(self),
+            left,
+            first: true,
+            prev: 0,
+            prev_delta: 0,
+        })
+    }
+}
+
+/// Decodes the floats of a Gorilla series, stops after the first error.
+pub struct GorillaIter<'r, 'a> {
+    bits: BitReader<'r, 'a>,
+    left: u64,
+    first: bool,
+    prev: u64,
+    window: Option<(u32, u32)>,
+}
+
+impl<'r, 'a> GorillaIter<'r, 'a> {
+    #[inline]
+    fn read_next(&mut self) -> Result<f64> {
+        if self.first {
+            self.first = false;
+            self.prev = self.bits.read_bits(64)?;
+            return Ok(f64::from_bits(self.prev));
+        }
+        if self.bits.read_bool()? {
+            if self.bits.read_bool()? {
+                let leading = self.bits.read_bits(5)? as u32;
+                let len = match self.bits.read_bits(6)? as u32 {
+                    0 => 64,
+                    len => len,
+                };
+                ensure!(
+                    leading + len <= 64,
+                    "gorilla window {}+{} > 64",
+                    leading,
+                    len
+                );
+                self.window = Some((leading, len));
+            }
+            let (leading, len) = match self.window {
+                Some(window) => window,
+                None => bail!("gorilla value reuses a missing window"),
+            };
+            self.prev ^= self.bits.read_bits(len)? << (64 - leading - len);
+        }
+        Ok(f64::from_bits(self.prev))
+    }
+}
+
+impl<'r, 'a> Iterator for GorillaIter<'r, 'a> {
+    type Item = Result<f64>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.left == 0 {
+            return None;
+        }
+        let v = self.read_next();
+        self.left = if v.is_err() { 0 } else { self.left - 1 };
+        Some(v)
+    }
+}
+
+/// Decodes the timestamps of a delta-of-delta series, stops after the first error.
+pub struct DeltaOfDeltaIter<'r, 'a> {
+    bits: BitReader<'r, 'a>,
+    left: u64,
+    first: bool,
+    prev: i64,
+    prev_delta: i64,
+}
+
+impl<'r, 'a> DeltaOfDeltaIter<'r, 'a> {
+    #[inline]
+    fn read_next(&mut self) -> Result<i64> {
+        if self.first {
+            self.first = false;
+            self.prev = self.bits.read_signed(64)?;
+            return Ok(self.prev);
+        }
+        let mut dod = 0;
+        if self.bits.read_bool()? {
+            let mut bucket = 0;
+            while bucket < DOD_BITS.len() - 1 && self.bits.read_bool()? {
+                bucket += 1;
+            }
+            dod = self.bits.read_signed(DOD_BITS[bucket])?;
+        }
+        self.prev_delta = self.prev_delta.wrapping_add(dod);
+        self.prev = self.prev.wrapping_add(self.prev_delta);
+        Ok(self.prev)
+    }
+}
+
+impl<'r, 'a> Iterator for DeltaOfDeltaIter<'r, 'a> {
+    type Item = Result<i64>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.left == 0 {
+            return None;
+        }
+        let v = self.read_next();
+        self.left = if v.is_err() { 0 } else { self.left - 1 };
+        Some(v)
+    }
+}
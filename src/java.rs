@@ -0,0 +1,170 @@
+//! Reading and writing the layout of Java's `DataOutputStream`/`DataInputStream`.
+//!
+//! Numbers are big endian whatever the `big_endian` feature,
+//! strings are `writeUTF`'s modified utf8. Use `Config::java()` for serde.
+
+use crate::{Data, DataReader};
+use anyhow::{bail, ensure, Result};
+
+/// a number written by `DataOutputStream` in big endian
+pub trait JavaNumber: Sized {
+    fn write_java(&self, data: &mut Data);
+    fn read_java(dr: &mut DataReader) -> Result<Self>;
+}
+
+macro_rules! impl_java_number {
+    ($($type:ty)+) => {
+        $(
+        impl JavaNumber for $type {
+            #[inline]
+            fn write_java(&self, data: &mut Data) {
+                data.write_buf(&self.to_be_bytes());
+            }
+
+            #[inline]
+            fn read_java(dr: &mut DataReader) -> Result<Self> {
+                let mut buff = [0; std::mem::size_of::<$type>()];
+                dr.read_buff(&mut buff)?;
+                Ok(<$type>::from_be_bytes(buff))
+            }
+        }
+        )*
+    };
+}
+
+impl_java_number!(i8 u8 i16 u16 i32 u32 i64 u64 i128 u128 f32 f64);
+
+impl JavaNumber for bool {
+    #[inline]
+    fn write_java(&self, data: &mut Data) {
+        data.write_fixed(*self);
+    }
+
+    #[inline]
+    fn read_java(dr: &mut DataReader) -> Result<Self> {
+        dr.read_fixed()
+    }
+}
+
+impl Data {
+    /// `writeInt`, `writeLong`, `writeDouble`.. in big endian
+    #[inline]
+    pub fn write_java_fixed(&mut self, v: impl JavaNumber) {
+        v.write_java(self)
+    }
+
+    /// `writeChar`, a Java char is one utf16 unit,
+    /// so chars outside the basic multilingual plane are rejected
+    #[inline]
+    pub fn write_java_char(&mut self, v: char) -> Result<()> {
+        ensure!(
+            (v as u32) <= 0xffff,
+            "char {:?} is not a single Java char",
+            v
+        );
+        self.write_java_fixed(v as u16);
+        Ok(())
+    }
+
+    /// `writeUTF`, modified utf8 prefixed by its u16 byte length,
+    /// NUL is written as two bytes and supplementary chars as a surrogate pair
+    #[inline]
+    pub fn write_java_utf(&mut self, v: &str) -> Result<()> {
+        let len_offset = self.len();
+        self.write_java_fixed(0u16);
+        let mut units = [0; 2];
+        for c in v.chars() {
+            for &unit in c.encode_utf16(&mut units).iter() {
+                match unit {
+                    0x01..=0x7f => self.write_fixed(unit as u8),
+                    0x00 | 0x80..=0x7ff => {
+                        self.write_fixed(0xc0 | (unit >> 6) as u8);
+                        self.write_fixed(0x80 | (unit & 0x3f) as u8);
+                    }
+                    _ => {
+                        self.write_fixed(0xe0 | (unit >> 12) as u8);
+                        self.write_fixed(0x80 | ((unit >> 6) & 0x3f) as u8);
+                        self.write_fixed(0x80 | (unit & 0x3f) as u8);
+                    }
+                }
+            }
+        }
+        let len = self.len() - len_offset - 2;
+        if len > u16::MAX as usize {
+            self.truncate(len_offset);
+            bail!("encoded string length {} too large for writeUTF", len);
+        }
+        self.write_buf_at(len_offset, &(len as u16).to_be_bytes())
+    }
+}
+
+impl<'a> DataReader<'a> {
+    /// `readInt`, `readLong`, `readDouble`.. in big endian
+    #[inline]
+    pub fn read_java_fixed<T: JavaNumber>(&mut self) -> Result<T> {
+        T::read_java(self)
+    }
+
+    /// `readChar`, a lone surrogate is not a Rust char and is rejected
+    #[inline]
+    pub fn read_java_char(&mut self) -> Result<char> {
+        let unit = self.read_java_fixed::<u16>()?;
+        match char::from_u32(unit as u32) {
+            Some(c) => Ok(c),
+            None => bail!("Java char {:#x} is a lone surrogate", unit),
+        }
+    }
+
+    /// `readUTF`
+    #[inline]
+    pub fn read_java_utf(&mut self) -> Result<String> {
+        let len = self.read_java_fixed::<u16>()? as usize;
+        ensure!(
+            len <= self.len(),
+            "read string size too big,{}>{}",
+            len,
+            self.len()
+        );
+        let (bytes, have) = self.buff.split_at(len);
+        self.buff = have;
+
+        let mut units = Vec::with_capacity(len);
+        let mut i = 0;
+        while i < bytes.len() {
+            let b = bytes[i];
+            let (unit, size) = match b {
+                0x00..=0x7f => (b as u16, 1),
+                0xc0..=0xdf => (((b as u16 & 0x1f) << 6) | continuation(bytes, i + 1)?, 2),
+                0xe0..=0xef => (
+                    ((b as u16 & 0x0f) << 12)
+                        | (continuation(bytes, i + 1)? << 6)
+                        | continuation(bytes, i + 2)?,
+                    3,
+                ),
+                _ => bail!("malformed modified utf8 byte {:#x} at {}", b, i),
+            };
+            units.push(unit);
+            i += size;
+        }
+        let mut res = String::with_capacity(len);
+        for c in char::decode_utf16(units) {
+            match c {
+                Ok(c) => res.push(c),
+                Err(err) => bail!(
+                    "modified utf8 has a lone surrogate {:#x}",
+                    err.unpaired_surrogate()
+                ),
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// the low 6 bits of a continuation byte
+#[inline]
+fn continuation(bytes: &[u8], i: usize) -> Result<u16> {
+    match bytes.get(i) {
+        Some(&b) if b & 0xc0 == 0x80 => Ok((b & 0x3f) as u16),
+        _ => bail!("malformed modified utf8 at {}", i),
+    }
+}
@@ -1,10 +1,24 @@
+pub mod bits;
+pub mod config;
 pub mod data;
 pub mod data_owned_reader;
 pub mod data_read;
+pub mod delta_var;
+pub mod dotnet;
+pub mod gorilla;
+pub mod java;
 pub mod pack;
+pub mod pack_fmt;
+pub mod protobuf;
 pub mod serde;
+pub mod tlv;
+pub mod varint_flavor;
 
+pub use config::*;
 pub use data::*;
 pub use data_owned_reader::*;
 pub use data_read::*;
+#[allow(unused_imports)]
 pub use pack::*;
+pub use serde::adapter::{fixed_array, u16_len_str, varint};
+pub use serde::columnar;
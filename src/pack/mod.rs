@@ -5,7 +5,6 @@ use serde::{Deserialize, Serialize};
 
 /// 这是为了兼容而写的,请不要随便使用
 /// This is written for compatibility. Please don't use it casually
-
 impl Data {
     #[cfg(all(feature = "rmp", not(feature = "json"), not(feature = "data")))]
     #[inline]
@@ -22,7 +21,7 @@ impl Data {
             self.truncate(bak);
             self.mode = 0;
             let buff = rmp_serde::encode::to_vec(&value)?;
-            self.write_fixed(buff);
+            self.try_write_fixed(buff)?;
         }
         Ok(())
     }
@@ -42,7 +41,7 @@ impl Data {
             self.truncate(bak);
             self.mode = 0;
             let buff = serde_json::to_vec(&value)?;
-            self.write_fixed(buff);
+            self.try_write_fixed(buff)?;
         }
         self.mode = 0;
         Ok(())
@@ -76,6 +75,7 @@ impl<'de, 'a: 'de> DataReader<'a> {
     #[inline]
     pub fn pack_deserialize<'b, T: Deserialize<'de>>(&'b mut self) -> Result<T> {
         let mut check_buff = DataReader::from(self.buff);
+        check_buff.config = self.config;
         check_buff.mode = 1;
         match check_buff.serde_deserialize() {
             Ok(value) => {
@@ -99,6 +99,7 @@ impl<'de, 'a: 'de> DataReader<'a> {
     #[inline]
     pub fn pack_deserialize<'b, T: Deserialize<'de>>(&'b mut self) -> Result<T> {
         let mut check_buff = DataReader::from(self.buff);
+        check_buff.config = self.config;
         check_buff.mode = 1;
         match check_buff.serde_deserialize() {
             Ok(value) => {
@@ -116,6 +117,7 @@ impl<'de, 'a: 'de> DataReader<'a> {
     #[inline]
     pub fn pack_to<'b, T: Deserialize<'de>>(&'b mut self) -> Result<T> {
         let mut rdata = DataReader::from(self.buff);
+        rdata.config = self.config;
         Ok(rdata.serde_deserialize()?)
     }
 
@@ -123,6 +125,7 @@ impl<'de, 'a: 'de> DataReader<'a> {
     #[inline]
     pub fn pack_deserialize<'b, T: Deserialize<'de>>(&'b mut self) -> Result<T> {
         let mut check_buff = DataReader::from(self.buff);
+        check_buff.config = self.config;
         check_buff.mode = 0;
         let value = check_buff.serde_deserialize()?;
         self.reload(check_buff.buff, check_buff.original_len);
@@ -141,6 +144,7 @@ impl DataOwnedReader {
     #[inline]
     pub fn pack_deserialize<'a, T: Deserialize<'a>>(&'a mut self) -> Result<T> {
         let mut dr = DataReader::from(&self.inner[self.offset..]);
+        dr.config = self.config;
         dr.mode = 1;
         match dr.serde_deserialize() {
             Ok(value) => {
@@ -166,6 +170,7 @@ impl DataOwnedReader {
     #[inline]
     pub fn pack_deserialize<'a, T: Deserialize<'a>>(&'a mut self) -> Result<T> {
         let mut dr = DataReader::from(&self.inner[self.offset..]);
+        dr.config = self.config;
         dr.mode = 1;
         match dr.serde_deserialize() {
             Ok(value) => {
@@ -184,7 +189,7 @@ impl DataOwnedReader {
     #[cfg(all(feature = "rmp", feature = "data"))]
     #[inline]
     pub fn pack_to<'a, T: Deserialize<'a>>(&'a mut self) -> Result<T> {
-        let mut rdata = DataReader::from(self);
+        let mut rdata = self.get_all_reader();
         Ok(rdata.serde_deserialize()?)
     }
 
@@ -192,6 +197,7 @@ impl DataOwnedReader {
     #[inline]
     pub fn pack_deserialize<'a, T: Deserialize<'a>>(&'a mut self) -> Result<T> {
         let mut dr = DataReader::from(&self.inner[self.offset..]);
+        dr.config = self.config;
         dr.mode = 0;
         let value = dr.serde_deserialize()?;
         self.offset += dr.offset();
@@ -0,0 +1,639 @@
+//! Python `struct` style format strings.
+//!
+//! The first char may set the byte order: `<` little endian, `>` or `!` big endian,
+//! `=` the crate's order (little endian unless the `big_endian` feature) and `@`,
+//! the default, the crate's order with numbers aligned to their size.
+//!
+//! Codes: `x` pad byte, `c` one byte, `b`/`B` i8/u8, `?` bool, `h`/`H` i16/u16,
+//! `i`/`I` and `l`/`L` i32/u32, `q`/`Q` i64/u64, `f` f32, `d` f64 and `s` bytes.
+//! A count before a code repeats it, except for `s` where it is the byte length,
+//! shorter bytes are padded with zeros and longer ones truncated.
+//! Whitespace between codes is ignored.
+//!
+//! The `pack_fmt!`/`unpack_fmt!` macros check the format against the types
+//! of a tuple when the crate using them is built.
+
+use crate::{Data, DataReader, ReadNumberFixed, WriteNumberFixed};
+use anyhow::{anyhow, bail, ensure, Result};
+use paste::paste;
+use std::convert::{TryFrom, TryInto};
+use std::marker::PhantomData;
+
+/// A value packed by `pack_fmt` or unpacked by `unpack_fmt`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FmtValue {
+    /// `b`, `h`, `i`, `l`, `q`
+    Int(i64),
+    /// `B`, `H`, `I`, `L`, `Q`
+    UInt(u64),
+    /// `f`, `d`
+    Float(f64),
+    /// `?`
+    Bool(bool),
+    /// `c`, `s`
+    Bytes(Vec<u8>),
+}
+
+macro_rules! impl_fmt_value_from {
+    ($($variant:ident $as:ty: $($type:ty)+;)+) => {
+        $($(
+        impl From<$type> for FmtValue {
+            #[inline]
+            fn from(v: $type) -> Self {
+                FmtValue::$variant(v as $as)
+            }
+        }
+        )+)+
+    };
+}
+
+impl_fmt_value_from! {
+    Int i64: i8 i16 i32 i64;
+    UInt u64: u8 u16 u32 u64;
+    Float f64: f32 f64;
+}
+
+impl From<bool> for FmtValue {
+    #[inline]
+    fn from(v: bool) -> Self {
+        FmtValue::Bool(v)
+    }
+}
+
+impl From<Vec<u8>> for FmtValue {
+    #[inline]
+    fn from(v: Vec<u8>) -> Self {
+        FmtValue::Bytes(v)
+    }
+}
+
+impl From<&[u8]> for FmtValue {
+    #[inline]
+    fn from(v: &[u8]) -> Self {
+        FmtValue::Bytes(v.to_vec())
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for FmtValue {
+    #[inline]
+    fn from(v: [u8; N]) -> Self {
+        FmtValue::Bytes(v.to_vec())
+    }
+}
+
+impl From<&str> for FmtValue {
+    #[inline]
+    fn from(v: &str) -> Self {
+        FmtValue::Bytes(v.as_bytes().to_vec())
+    }
+}
+
+/// one code of a format and its count
+#[derive(Debug, Clone, Copy)]
+struct Item {
+    code: u8,
+    count: usize,
+}
+
+/// byte size of a code, 0 if it is not supported
+#[inline]
+const fn code_size(code: u8) -> usize {
+    match code {
+        b'x' | b'c' | b'b' | b'B' | b'?' | b's' => 1,
+        b'h' | b'H' => 2,
+        b'i' | b'I' | b'l' | b'L' | b'f' => 4,
+        b'q' | b'Q' | b'd' => 8,
+        _ => 0,
+    }
+}
+
+/// big endian, aligned and the position after the byte order char
+#[inline]
+const fn parse_order(fmt: &[u8]) -> (bool, bool, usize) {
+    let native = cfg!(feature = "big_endian");
+    if fmt.is_empty() {
+        return (native, true, 0);
+    }
+    match fmt[0] {
+        b'<' => (false, false, 1),
+        b'>' | b'!' => (true, false, 1),
+        b'=' => (native, false, 1),
+        b'@' => (native, true, 1),
+        _ => (native, true, 0),
+    }
+}
+
+/// the item at `pos` and the position after it, none at the end of the format
+const fn parse_item(fmt: &[u8], mut pos: usize) -> Result<Option<(Item, usize)>, &'static str> {
+    while pos < fmt.len() && fmt[pos].is_ascii_whitespace() {
+        pos += 1;
+    }
+    if pos == fmt.len() {
+        return Ok(None);
+    }
+    let mut count = 1usize;
+    if fmt[pos].is_ascii_digit() {
+        count = 0;
+        while pos < fmt.len() && fmt[pos].is_ascii_digit() {
+            count = match count.checked_mul(10) {
+                Some(v) => match v.checked_add((fmt[pos] - b'0') as usize) {
+                    Some(v) => v,
+                    None => return Err("repeat count too large"),
+                },
+                None => return Err("repeat count too large"),
+            };
+            pos += 1;
+        }
+        if pos == fmt.len() {
+            return Err("repeat count given without format code");
+        }
+    }
+    let code = fmt[pos];
+    if code_size(code) == 0 {
+        return Err("bad format code");
+    }
+    Ok(Some((Item { code, count }, pos + 1)))
+}
+
+/// big endian, aligned and the items of a format
+fn parse(fmt: &str) -> Result<(bool, bool, Vec<Item>)> {
+    let fmt = fmt.as_bytes();
+    let (big, align, mut pos) = parse_order(fmt);
+    let mut items = Vec::new();
+    loop {
+        match parse_item(fmt, pos) {
+            Ok(Some((item, next))) => {
+                items.push(item);
+                pos = next;
+            }
+            Ok(None) => return Ok((big, align, items)),
+            Err(err) => bail!(
+                "format {:?}: {} at {}",
+                String::from_utf8_lossy(fmt),
+                err,
+                pos
+            ),
+        }
+    }
+}
+
+/// padding before a number aligned to its size
+#[inline]
+fn align_pad(align: bool, code: u8, offset: usize) -> usize {
+    let size = code_size(code);
+    if align && size > 1 && code != b's' {
+        (size - offset % size) % size
+    } else {
+        0
+    }
+}
+
+/// byte size of a format, like Python's `struct.calcsize`
+pub fn calcsize(fmt: &str) -> Result<usize> {
+    let (_, align, items) = parse(fmt)?;
+    let mut size = 0usize;
+    for item in items {
+        size += align_pad(align, item.code, size);
+        size = item
+            .count
+            .checked_mul(code_size(item.code))
+            .and_then(|len| len.checked_add(size))
+            .ok_or_else(|| anyhow!("format {:?} size overflow", fmt))?;
+    }
+    Ok(size)
+}
+
+#[inline]
+fn put<T: WriteNumberFixed>(data: &mut Data, big: bool, v: T) {
+    let start = data.len();
+    data.write_fixed(v);
+    if big != cfg!(feature = "big_endian") {
+        data[start..].reverse();
+    }
+}
+
+#[inline]
+fn zeros(data: &mut Data, len: usize) {
+    let end = data.len() + len;
+    data.resize(end, 0);
+}
+
+#[inline]
+fn get<T: ReadNumberFixed>(dr: &mut DataReader, big: bool, size: usize) -> Result<T> {
+    let mut buff = [0; 8];
+    let buff = &mut buff[..size];
+    dr.read_buff(buff)?;
+    if big != cfg!(feature = "big_endian") {
+        buff.reverse();
+    }
+    DataReader::from(&*buff).read_fixed()
+}
+
+#[inline]
+fn int<T: TryFrom<i64> + TryFrom<u64>>(code: u8, v: &FmtValue) -> Result<T> {
+    let res = match *v {
+        FmtValue::Int(i) => <T as TryFrom<i64>>::try_from(i).ok(),
+        FmtValue::UInt(u) => <T as TryFrom<u64>>::try_from(u).ok(),
+        _ => bail!("'{}' needs an integer, got {:?}", code as char, v),
+    };
+    res.ok_or_else(|| anyhow!("{:?} out of range for '{}'", v, code as char))
+}
+
+#[inline]
+fn float(code: u8, v: &FmtValue) -> Result<f64> {
+    match *v {
+        FmtValue::Float(f) => Ok(f),
+        FmtValue::Int(i) => Ok(i as f64),
+        FmtValue::UInt(u) => Ok(u as f64),
+        _ => bail!("'{}' needs a number, got {:?}", code as char, v),
+    }
+}
+
+fn pack_value(data: &mut Data, code: u8, big: bool, v: &FmtValue) -> Result<()> {
+    match code {
+        b'c' => match v {
+            FmtValue::Bytes(b) if b.len() == 1 => data.write_fixed(b[0]),
+            _ => data.write_fixed(int::<u8>(code, v)?),
+        },
+        b'?' => match v {
+            FmtValue::Bool(b) => data.write_fixed(*b),
+            _ => bail!("'?' needs a bool, got {:?}", v),
+        },
+        b'b' => put(data, big, int::<i8>(code, v)?),
+        b'B' => put(data, big, int::<u8>(code, v)?),
+        b'h' => put(data, big, int::<i16>(code, v)?),
+        b'H' => put(data, big, int::<u16>(code, v)?),
+        b'i' | b'l' => put(data, big, int::<i32>(code, v)?),
+        b'I' | b'L' => put(data, big, int::<u32>(code, v)?),
+        b'q' => put(data, big, int::<i64>(code, v)?),
+        b'Q' => put(data, big, int::<u64>(code, v)?),
+        b'f' => put(data, big, float(code, v)? as f32),
+        b'd' => put(data, big, float(code, v)?),
+        _ => bail!("'{}' is not a value code", code as char),
+    }
+    Ok(())
+}
+
+fn pack(data: &mut Data, fmt: &str, values: &[FmtValue]) -> Result<()> {
+    let (big, align, items) = parse(fmt)?;
+    let start = data.len();
+    let mut values = values.iter();
+    let mut next = || {
+        values
+            .next()
+            .ok_or_else(|| anyhow!("format {:?} needs more values", fmt))
+    };
+    for item in items {
+        let pad = align_pad(align, item.code, data.len() - start);
+        zeros(data, pad);
+        match item.code {
+            b'x' => zeros(data, item.count),
+            b's' => match next()? {
+                FmtValue::Bytes(b) => {
+                    let len = b.len().min(item.count);
+                    data.write_buf(&b[..len]);
+                    zeros(data, item.count - len);
+                }
+                v => bail!("'s' needs bytes, got {:?}", v),
+            },
+            code => {
+                for _ in 0..item.count {
+                    pack_value(data, code, big, next()?)?;
+                }
+            }
+        }
+    }
+    ensure!(
+        values.len() == 0,
+        "format {:?} has {} values left over",
+        fmt,
+        values.len()
+    );
+    Ok(())
+}
+
+fn unpack_value(dr: &mut DataReader, code: u8, big: bool) -> Result<FmtValue> {
+    Ok(match code {
+        b'c' => FmtValue::Bytes(vec![dr.read_fixed::<u8>()?]),
+        b'?' => FmtValue::Bool(dr.read_fixed::<u8>()? != 0),
+        b'b' => FmtValue::Int(get::<i8>(dr, big, 1)? as i64),
+        b'B' => FmtValue::UInt(get::<u8>(dr, big, 1)? as u64),
+        b'h' => FmtValue::Int(get::<i16>(dr, big, 2)? as i64),
+        b'H' => FmtValue::UInt(get::<u16>(dr, big, 2)? as u64),
+        b'i' | b'l' => FmtValue::Int(get::<i32>(dr, big, 4)? as i64),
+        b'I' | b'L' => FmtValue::UInt(get::<u32>(dr, big, 4)? as u64),
+        b'q' => FmtValue::Int(get::<i64>(dr, big, 8)?),
+        b'Q' => FmtValue::UInt(get::<u64>(dr, big, 8)?),
+        b'f' => FmtValue::Float(get::<f32>(dr, big, 4)? as f64),
+        b'd' => FmtValue::Float(get::<f64>(dr, big, 8)?),
+        _ => bail!("'{}' is not a value code", code as char),
+    })
+}
+
+impl Data {
+    /// write `values` laid out by `fmt`, like Python's `struct.pack`,
+    /// nothing is written on error
+    #[inline]
+    pub fn pack_fmt(&mut self, fmt: &str, values: &[FmtValue]) -> Result<()> {
+        let start = self.len();
+        let res = pack(self, fmt, values);
+        if res.is_err() {
+            self.truncate(start);
+        }
+        res
+    }
+}
+
+impl<'a> DataReader<'a> {
+    /// read the values laid out by `fmt`, like Python's `struct.unpack`
+    #[inline]
+    pub fn unpack_fmt(&mut self, fmt: &str) -> Result<Vec<FmtValue>> {
+        let size = calcsize(fmt)?;
+        ensure!(
+            size <= self.len(),
+            "format {:?} needs {} bytes,{} left",
+            fmt,
+            size,
+            self.len()
+        );
+        let (big, align, items) = parse(fmt)?;
+        let start = self.offset();
+        let mut values = Vec::new();
+        for item in items {
+            self.advance(align_pad(align, item.code, self.offset() - start))?;
+            match item.code {
+                b'x' => self.advance(item.count)?,
+                b's' => {
+                    let (bytes, have) = self.buff.split_at(item.count);
+                    self.buff = have;
+                    values.push(FmtValue::Bytes(bytes.to_vec()));
+                }
+                code => {
+                    for _ in 0..item.count {
+                        values.push(unpack_value(self, code, big)?);
+                    }
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// A type of the tuples of `pack_fmt!`/`unpack_fmt!`.
+pub trait FmtField: Sized {
+    /// the format codes of the type
+    const CODES: &'static [u8];
+    /// the byte length of an `s` field
+    const LEN: usize = 0;
+    fn into_value(self) -> FmtValue;
+    fn from_value(v: FmtValue) -> Result<Self>;
+}
+
+macro_rules! impl_fmt_field_int {
+    ($($type:ty: $codes:literal;)+) => {
+        $(
+        impl FmtField for $type {
+            const CODES: &'static [u8] = $codes;
+
+            #[inline]
+            fn into_value(self) -> FmtValue {
+                self.into()
+            }
+
+            #[inline]
+            fn from_value(v: FmtValue) -> Result<Self> {
+                match v {
+                    FmtValue::Bytes(b) if b.len() == 1 && Self::CODES.contains(&b'c') => Ok(b[0] as $type),
+                    v => int(Self::CODES[0], &v),
+                }
+            }
+        }
+        )+
+    };
+}
+
+impl_fmt_field_int! {
+    i8: b"b";
+    u8: b"Bc";
+    i16: b"h";
+    u16: b"H";
+    i32: b"il";
+    u32: b"IL";
+    i64: b"q";
+    u64: b"Q";
+}
+
+macro_rules! impl_fmt_field_float {
+    ($($type:ty: $codes:literal;)+) => {
+        $(
+        impl FmtField for $type {
+            const CODES: &'static [u8] = $codes;
+
+            #[inline]
+            fn into_value(self) -> FmtValue {
+                self.into()
+            }
+
+            #[inline]
+            fn from_value(v: FmtValue) -> Result<Self> {
+                Ok(float(Self::CODES[0], &v)? as $type)
+            }
+        }
+        )+
+    };
+}
+
+impl_fmt_field_float! {
+    f32: b"f";
+    f64: b"d";
+}
+
+impl FmtField for bool {
+    const CODES: &'static [u8] = b"?";
+
+    #[inline]
+    fn into_value(self) -> FmtValue {
+        FmtValue::Bool(self)
+    }
+
+    #[inline]
+    fn from_value(v: FmtValue) -> Result<Self> {
+        match v {
+            FmtValue::Bool(b) => Ok(b),
+            v => bail!("'?' needs a bool, got {:?}", v),
+        }
+    }
+}
+
+impl<const N: usize> FmtField for [u8; N] {
+    const CODES: &'static [u8] = b"s";
+    const LEN: usize = N;
+
+    #[inline]
+    fn into_value(self) -> FmtValue {
+        FmtValue::Bytes(self.to_vec())
+    }
+
+    #[inline]
+    fn from_value(v: FmtValue) -> Result<Self> {
+        match v {
+            FmtValue::Bytes(b) => b
+                .try_into()
+                .map_err(|b: Vec<u8>| anyhow!("{} bytes for a [u8; {}]", b.len(), N)),
+            v => bail!("'s' needs bytes, got {:?}", v),
+        }
+    }
+}
+
+/// A tuple of `FmtField` values for `pack_fmt!`/`unpack_fmt!`.
+pub trait FmtTuple: Sized {
+    /// the codes and `s` length of each field
+    const FIELDS: &'static [(&'static [u8], usize)];
+    fn into_values(self) -> Vec<FmtValue>;
+    fn from_values(values: Vec<FmtValue>) -> Result<Self>;
+}
+
+impl FmtTuple for () {
+    const FIELDS: &'static [(&'static [u8], usize)] = &[];
+
+    #[inline]
+    fn into_values(self) -> Vec<FmtValue> {
+        Vec::new()
+    }
+
+    #[inline]
+    fn from_values(_values: Vec<FmtValue>) -> Result<Self> {
+        Ok(())
+    }
+}
+
+macro_rules! impl_fmt_tuple {
+    ($($name:ident)+) => {
+      paste!{
+        impl<$($name: FmtField),+> FmtTuple for ($($name,)+) {
+            const FIELDS: &'static [(&'static [u8], usize)] = &[$(($name::CODES, $name::LEN)),+];
+
+            #[inline]
+            fn into_values(self) -> Vec<FmtValue> {
+                let ($([<$name:lower>],)+) = self;
+                vec![$([<$name:lower>].into_value()),+]
+            }
+
+            #[inline]
+            fn from_values(values: Vec<FmtValue>) -> Result<Self> {
+                let mut values = values.into_iter();
+                Ok(($($name::from_value(
+                    values.next().ok_or_else(|| anyhow!("too few values for the tuple"))?,
+                )?,)+))
+            }
+        }
+      }
+    };
+}
+
+macro_rules! impl_fmt_tuples {
+    ($first:ident $($rest:ident)*) => {
+        impl_fmt_tuple!($first $($rest)*);
+        impl_fmt_tuples!($($rest)*);
+    };
+    () => {};
+}
+
+impl_fmt_tuples!(P O N M L K J I H G F E D C B A);
+
+/// panics, at compile time in the macros, if `fields` don't match `fmt`
+const fn check_fields(fmt: &[u8], fields: &[(&[u8], usize)]) {
+    let (_, _, mut pos) = parse_order(fmt);
+    let mut field = 0;
+    loop {
+        let (item, next) = match parse_item(fmt, pos) {
+            Ok(Some(item)) => item,
+            Ok(None) => break,
+            Err(err) => panic!("{}", err),
+        };
+        pos = next;
+        if item.code == b'x' {
+            continue;
+        }
+        let values = if item.code == b's' { 1 } else { item.count };
+        let mut i = 0;
+        while i < values {
+            if field == fields.len() {
+                panic!("the format has more values than the tuple");
+            }
+            let (codes, len) = fields[field];
+            let mut found = false;
+            let mut c = 0;
+            while c < codes.len() {
+                found |= codes[c] == item.code;
+                c += 1;
+            }
+            if !found {
+                panic!("a tuple field type doesn't match its format code");
+            }
+            if item.code == b's' && len != item.count {
+                panic!("an `s` length doesn't match its array length");
+            }
+            field += 1;
+            i += 1;
+        }
+    }
+    if field != fields.len() {
+        panic!("the tuple has more fields than the format");
+    }
+}
+
+/// the format string of a macro call
+#[doc(hidden)]
+pub trait FmtStr {
+    const FMT: &'static str;
+}
+
+#[doc(hidden)]
+pub struct FmtCheck<F, T>(PhantomData<(F, T)>);
+
+impl<F: FmtStr, T: FmtTuple> FmtCheck<F, T> {
+    const OK: () = check_fields(F::FMT.as_bytes(), T::FIELDS);
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn pack_typed<F: FmtStr, T: FmtTuple>(data: &mut Data, values: T) -> Result<()> {
+    #[allow(clippy::let_unit_value)]
+    let () = FmtCheck::<F, T>::OK;
+    data.pack_fmt(F::FMT, &values.into_values())
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn unpack_typed<F: FmtStr, T: FmtTuple>(dr: &mut DataReader) -> Result<T> {
+    #[allow(clippy::let_unit_value)]
+    let () = FmtCheck::<F, T>::OK;
+    T::from_values(dr.unpack_fmt(F::FMT)?)
+}
+
+/// `Data::pack_fmt` taking typed values, the format and the value types
+/// are checked when building: `pack_fmt!(data, "<IH", 1u32, 2u16)`
+#[macro_export]
+macro_rules! pack_fmt {
+    ($data:expr, $fmt:literal $(, $value:expr)* $(,)?) => {{
+        struct Fmt;
+        impl $crate::pack_fmt::FmtStr for Fmt {
+            const FMT: &'static str = $fmt;
+        }
+        $crate::pack_fmt::pack_typed::<Fmt, _>(&mut $data, ($($value,)*))
+    }};
+}
+
+/// `DataReader::unpack_fmt` returning a typed tuple, the format and the tuple types
+/// are checked when building: `let (a, b): (u32, u16) = unpack_fmt!(reader, "<IH")?`
+#[macro_export]
+macro_rules! unpack_fmt {
+    ($reader:expr, $fmt:literal) => {{
+        struct Fmt;
+        impl $crate::pack_fmt::FmtStr for Fmt {
+            const FMT: &'static str = $fmt;
+        }
+        $crate::pack_fmt::unpack_typed::<Fmt, _>(&mut $reader)
+    }};
+}
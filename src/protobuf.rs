@@ -0,0 +1,187 @@
+//! Protobuf wire format primitives, for hand written encoders and decoders.
+//!
+//! Varints are `write_var_integer`/`read_var_integer`, which zigzag signed values
+//! like `sint32`/`sint64`. For `int32`/`int64` write the value as `u64`
+//! (`v as i64 as u64`), a negative value then takes 10 bytes.
+
+use crate::{Data, DataReader};
+use anyhow::{bail, ensure, Error, Result};
+use std::convert::TryFrom;
+
+/// largest field number, `2^29 - 1`
+pub const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+
+/// deepest group nesting `skip_field` accepts, protobuf's default recursion limit
+pub const MAX_GROUP_DEPTH: usize = 100;
+
+/// the low 3 bits of a field key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireType {
+    Varint = 0,
+    Fixed64 = 1,
+    LengthDelimited = 2,
+    StartGroup = 3,
+    EndGroup = 4,
+    Fixed32 = 5,
+}
+
+impl TryFrom<u32> for WireType {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(value: u32) -> Result<Self> {
+        Ok(match value {
+            0 => WireType::Varint,
+            1 => WireType::Fixed64,
+            2 => WireType::LengthDelimited,
+            3 => WireType::StartGroup,
+            4 => WireType::EndGroup,
+            5 => WireType::Fixed32,
+            _ => bail!("invalid wire type:{}", value),
+        })
+    }
+}
+
+impl Data {
+    /// write the key of a field
+    #[inline]
+    pub fn write_tag(&mut self, field_number: u32, wire_type: WireType) -> Result<()> {
+        ensure!(
+            (1..=MAX_FIELD_NUMBER).contains(&field_number),
+            "invalid field number:{}",
+            field_number
+        );
+        self.write_var_integer((field_number << 3) | wire_type as u32);
+        Ok(())
+    }
+
+    /// write a `fixed32`, `sfixed32` (`v as u32`) or `float` (`v.to_bits()`),
+    /// always little endian
+    #[inline]
+    pub fn write_fixed32(&mut self, v: u32) {
+        self.write_buf(&v.to_le_bytes());
+    }
+
+    /// write a `fixed64`, `sfixed64` (`v as u64`) or `double` (`v.to_bits()`),
+    /// always little endian
+    #[inline]
+    pub fn write_fixed64(&mut self, v: u64) {
+        self.write_buf(&v.to_le_bytes());
+    }
+
+    /// write a length delimited sub-message, the closure writes its fields
+    /// and the varint length is inserted before them, nothing is written on error
+    #[inline]
+    pub fn write_message<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Data) -> Result<()>,
+    {
+        let start = self.len();
+        let res = write_message(self, f);
+        if res.is_err() {
+            self.truncate(start);
+        }
+        res
+    }
+}
+
+#[inline]
+fn write_message<F>(data: &mut Data, f: F) -> Result<()>
+where
+    F: FnOnce(&mut Data) -> Result<()>,
+{
+    let len_offset = data.reserve_var_len();
+    let start = data.begin_len();
+    f(data)?;
+    let len = data.end_len(start)?;
+    data.patch_var_len(len_offset, len)
+}
+
+impl<'a> DataReader<'a> {
+    /// read the key of a field, returns the field number and wire type
+    #[inline]
+    pub fn read_tag(&mut self) -> Result<(u32, WireType)> {
+        let key = self.read_var_integer::<u64>()?;
+        let field_number = key >> 3;
+        ensure!(
+            (1..=MAX_FIELD_NUMBER as u64).contains(&field_number),
+            "invalid field number:{}",
+            field_number
+        );
+        Ok((field_number as u32, WireType::try_from((key & 7) as u32)?))
+    }
+
+    /// read a `fixed32`, little endian
+    #[inline]
+    pub fn read_fixed32(&mut self) -> Result<u32> {
+        let mut buff = [0; 4];
+        self.read_buff(&mut buff)?;
+        Ok(u32::from_le_bytes(buff))
+    }
+
+    /// read a `fixed64`, little endian
+    #[inline]
+    pub fn read_fixed64(&mut self) -> Result<u64> {
+        let mut buff = [0; 8];
+        self.read_buff(&mut buff)?;
+        Ok(u64::from_le_bytes(buff))
+    }
+
+    /// read the bytes of a length delimited field
+    #[inline]
+    pub fn read_len_delimited(&mut self) -> Result<&'a [u8]> {
+        self.read_var_buf()
+    }
+
+    /// read a length delimited sub-message, returns a reader over its fields
+    #[inline]
+    pub fn read_message(&mut self) -> Result<DataReader<'a>> {
+        let mut reader = DataReader::from(self.read_var_buf()?);
+        reader.config = self.config;
+        Ok(reader)
+    }
+
+    /// skip the value of a field whose key was just read,
+    /// a group is skipped up to the end group of the same field
+    #[inline]
+    pub fn skip_field(&mut self, field: u32, wire_type: WireType) -> Result<()> {
+        // the field numbers of the groups being skipped, innermost last
+        let mut groups = Vec::new();
+        let (mut field, mut wire_type) = (field, wire_type);
+        loop {
+            match wire_type {
+                WireType::Varint => {
+                    self.read_var_integer::<u64>()?;
+                }
+                WireType::Fixed64 => self.advance(8)?,
+                WireType::LengthDelimited => {
+                    self.read_var_buf()?;
+                }
+                WireType::StartGroup => {
+                    ensure!(
+                        groups.len() < MAX_GROUP_DEPTH,
+                        "groups nested deeper than {}",
+                        MAX_GROUP_DEPTH
+                    );
+                    groups.push(field);
+                }
+                WireType::EndGroup => match groups.pop() {
+                    Some(start) => ensure!(
+                        start == field,
+                        "end group {} doesn't match start group {}",
+                        field,
+                        start
+                    ),
+                    None => bail!("unexpected end group"),
+                },
+                WireType::Fixed32 => self.advance(4)?,
+            }
+            if groups.is_empty() {
+                return Ok(());
+            }
+            let (next, next_type) = self.read_tag()?;
+            field = next;
+            wire_type = next_type;
+        }
+    }
+}
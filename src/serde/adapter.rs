@@ -0,0 +1,177 @@
+//! Field adapters for `#[serde(with = "...")]`, changing the encoding of one field.
+//!
+//! An adapter wraps the field in a newtype struct with a reserved name,
+//! `Data`/`DataReader` recognize the name and switch encoding while the field
+//! is written or read. Other serializers see a plain newtype struct.
+//! Tagged output stays self-describing, so adapters have no effect with `Config::tagged`.
+
+use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeTuple, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+
+const VARINT: &str = "$data_rw::varint";
+const U16_LEN_STR: &str = "$data_rw::u16_len_str";
+const FIXED_ARRAY: &str = "$data_rw::fixed_array";
+
+/// the adapter of the field being written or read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Adapter {
+    #[default]
+    None,
+    Varint,
+    U16LenStr,
+    FixedArray,
+}
+
+impl Adapter {
+    #[inline]
+    pub(crate) fn from_name(name: &str) -> Option<Adapter> {
+        match name {
+            VARINT => Some(Adapter::Varint),
+            U16_LEN_STR => Some(Adapter::U16LenStr),
+            FIXED_ARRAY => Some(Adapter::FixedArray),
+            _ => None,
+        }
+    }
+}
+
+/// deserialize `T` from the content of the adapter's newtype struct
+struct NewtypeVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for NewtypeVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("newtype struct")
+    }
+
+    #[inline]
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer)
+    }
+}
+
+/// Write the 16, 32 and 64 bit integers of the field as varint, zigzag for signed.
+/// Applies to nested values too, e.g. every element of a `Vec<u32>`.
+pub mod varint {
+    use super::*;
+
+    #[inline]
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(VARINT, value)
+    }
+
+    #[inline]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(VARINT, NewtypeVisitor(PhantomData))
+    }
+}
+
+/// Prefix the strings of the field with a u16 length,
+/// writing a string longer than `u16::MAX` fails.
+pub mod u16_len_str {
+    use super::*;
+
+    #[inline]
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(U16_LEN_STR, value)
+    }
+
+    #[inline]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(U16_LEN_STR, NewtypeVisitor(PhantomData))
+    }
+}
+
+/// Write a `[T; N]` of any size as its elements only, the length is known by the reader.
+pub mod fixed_array {
+    use super::*;
+    use std::convert::TryInto;
+
+    struct Array<'a, T, const N: usize>(&'a [T; N]);
+
+    impl<'a, T: Serialize, const N: usize> Serialize for Array<'a, T, N> {
+        #[inline]
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut tuple = serializer.serialize_tuple(N)?;
+            for value in self.0 {
+                tuple.serialize_element(value)?;
+            }
+            tuple.end()
+        }
+    }
+
+    struct ArrayVisitor<T, const N: usize>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>, const N: usize> Visitor<'de> for ArrayVisitor<T, N> {
+        type Value = [T; N];
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "an array of length {}", N)
+        }
+
+        #[inline]
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_tuple(N, self)
+        }
+
+        #[inline]
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut values = Vec::with_capacity(N);
+            while let Some(value) = seq.next_element()? {
+                if values.len() == N {
+                    return Err(A::Error::invalid_length(N + 1, &self));
+                }
+                values.push(value);
+            }
+            let len = values.len();
+            values
+                .try_into()
+                .map_err(|_| A::Error::invalid_length(len, &self))
+        }
+    }
+
+    #[inline]
+    pub fn serialize<T, S, const N: usize>(value: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(FIXED_ARRAY, &Array(value))
+    }
+
+    #[inline]
+    pub fn deserialize<'de, T, D, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(FIXED_ARRAY, ArrayVisitor::<T, N>(PhantomData))
+    }
+}
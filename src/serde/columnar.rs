@@ -0,0 +1,835 @@
+//! Columnar layout for a sequence of structs, one column per field.
+//!
+//! Numbers and bools of a field are contiguous fixed values, strings and bytes
+//! an offsets table and their contents, other values (options, enums, nested structs..)
+//! an offsets table and the values written with the config. A single column can be
+//! read without decoding the rows, see `DataReader::read_column`.
+//!
+//! Layout: the row and column counts as lengths, then for each column its name,
+//! a type tag and its content. A fixed column is one value per row, the others
+//! one u32 end offset per row followed by the bytes they index.
+//!
+//! Rows are structs with the same fields in the same order, `skip_serializing_if`
+//! is rejected. Use `Data::write_columnar`/`DataReader::read_columnar`, or
+//! `#[serde(with = "data_rw::columnar")]` on a `Vec` field, which writes the block
+//! as bytes with the default config.
+
+use crate::serde::adapter::Adapter;
+use crate::serde::error::DataError;
+use crate::serde::tag;
+use crate::{Config, Data, DataReader};
+use anyhow::anyhow;
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{DeserializeSeed, MapAccess, Visitor};
+use serde::ser::{Impossible, SerializeStruct};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// tag of a column of values written with the config
+const VALUE: u8 = 0xff;
+
+/// byte width of the values of a fixed column
+#[inline]
+fn fixed_width(tag: u8) -> Option<usize> {
+    Some(match tag {
+        tag::TRUE | tag::I8 | tag::U8 => 1,
+        tag::I16 | tag::U16 => 2,
+        tag::I32 | tag::U32 | tag::F32 => 4,
+        tag::I64 | tag::U64 | tag::F64 => 8,
+        tag::I128 | tag::U128 => 16,
+        _ => return None,
+    })
+}
+
+/// a column being written
+struct ColumnWriter {
+    name: &'static str,
+    tag: Option<u8>,
+    /// fixed values, or the bytes indexed by `offsets`
+    values: Data,
+    offsets: Vec<u32>,
+}
+
+impl ColumnWriter {
+    #[inline]
+    fn set_tag(&mut self, tag: u8) -> Result<(), DataError> {
+        match self.tag {
+            None => self.tag = Some(tag),
+            Some(old) if old != tag => {
+                return Err(anyhow!("column {} mixes value types", self.name).into())
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn push_offset(&mut self) -> Result<(), DataError> {
+        let offset = u32::try_from(self.values.len())
+            .map_err(|_| anyhow!("column {} larger than 4GB", self.name))?;
+        self.offsets.push(offset);
+        Ok(())
+    }
+
+    /// write a field value, falling back to the config's layout
+    /// for anything but numbers, bools, strings and bytes
+    #[inline]
+    fn write<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DataError> {
+        let start = self.values.len();
+        match value.serialize(CellSerializer { column: self }) {
+            Err(DataError::RollBack) => {
+                self.values.truncate(start);
+                self.set_tag(VALUE)?;
+                value.serialize(&mut self.values)?;
+                self.push_offset()
+            }
+            res => res,
+        }
+    }
+}
+
+/// Collects the fields of each row into columns.
+struct Columns {
+    columns: Vec<ColumnWriter>,
+    /// rows written so far
+    rows: usize,
+    field: usize,
+    config: Config,
+}
+
+#[inline]
+fn not_struct() -> DataError {
+    anyhow!("columnar rows must be structs").into()
+}
+
+macro_rules! not_struct {
+    ($($method:ident($($arg:ty),*);)+) => {
+        $(
+        #[inline]
+        fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Self::Error> {
+            Err(not_struct())
+        }
+        )+
+    };
+}
+
+impl Serializer for &mut Columns {
+    type Ok = ();
+    type Error = DataError;
+    type SerializeSeq = Impossible<(), DataError>;
+    type SerializeTuple = Impossible<(), DataError>;
+    type SerializeTupleStruct = Impossible<(), DataError>;
+    type SerializeTupleVariant = Impossible<(), DataError>;
+    type SerializeMap = Impossible<(), DataError>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), DataError>;
+
+    not_struct! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_i128(i128);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_u128(u128);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    #[inline]
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(not_struct())
+    }
+
+    #[inline]
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(not_struct())
+    }
+
+    #[inline]
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(not_struct())
+    }
+
+    #[inline]
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(not_struct())
+    }
+
+    #[inline]
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(not_struct())
+    }
+
+    #[inline]
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(not_struct())
+    }
+
+    #[inline]
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(not_struct())
+    }
+
+    #[inline]
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.field = 0;
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(not_struct())
+    }
+}
+
+impl SerializeStruct for &mut Columns {
+    type Ok = ();
+    type Error = DataError;
+
+    #[inline]
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.field == self.columns.len() {
+            if self.rows > 0 {
+                return Err(anyhow!("columnar row has a new field {}", key).into());
+            }
+            let mut values = Data::new();
+            values.set_config(self.config);
+            self.columns.push(ColumnWriter {
+                name: key,
+                tag: None,
+                values,
+                offsets: Vec::new(),
+            });
+        }
+        let column = &mut self.columns[self.field];
+        if column.name != key {
+            return Err(anyhow!("columnar row field {} != {}", key, column.name).into());
+        }
+        column.write(value)?;
+        self.field += 1;
+        Ok(())
+    }
+
+    #[inline]
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+        Err(anyhow!("columnar rows can't skip field {}", key).into())
+    }
+
+    #[inline]
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        if self.field != self.columns.len() {
+            return Err(anyhow!(
+                "columnar row has {} fields, expected {}",
+                self.field,
+                self.columns.len()
+            )
+            .into());
+        }
+        self.rows += 1;
+        Ok(())
+    }
+}
+
+/// Writes a field value to its column if it is a number, bool, string or bytes,
+/// returns `DataError::RollBack` for anything else.
+struct CellSerializer<'a> {
+    column: &'a mut ColumnWriter,
+}
+
+macro_rules! cell_fixed {
+    ($($method:ident $type:ty: $tag:expr;)+) => {
+        $(
+        #[inline]
+        fn $method(self, v: $type) -> Result<Self::Ok, Self::Error> {
+            self.column.set_tag($tag)?;
+            self.column.values.write_fixed(v);
+            Ok(())
+        }
+        )+
+    };
+}
+
+macro_rules! cell_rollback {
+    ($($method:ident($($arg:ty),*);)+) => {
+        $(
+        #[inline]
+        fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Self::Error> {
+            Err(DataError::RollBack)
+        }
+        )+
+    };
+}
+
+impl<'a> Serializer for CellSerializer<'a> {
+    type Ok = ();
+    type Error = DataError;
+    type SerializeSeq = Impossible<(), DataError>;
+    type SerializeTuple = Impossible<(), DataError>;
+    type SerializeTupleStruct = Impossible<(), DataError>;
+    type SerializeTupleVariant = Impossible<(), DataError>;
+    type SerializeMap = Impossible<(), DataError>;
+    type SerializeStruct = Impossible<(), DataError>;
+    type SerializeStructVariant = Impossible<(), DataError>;
+
+    cell_fixed! {
+        serialize_bool bool: tag::TRUE;
+        serialize_i8 i8: tag::I8;
+        serialize_i16 i16: tag::I16;
+        serialize_i32 i32: tag::I32;
+        serialize_i64 i64: tag::I64;
+        serialize_i128 i128: tag::I128;
+        serialize_u8 u8: tag::U8;
+        serialize_u16 u16: tag::U16;
+        serialize_u32 u32: tag::U32;
+        serialize_u64 u64: tag::U64;
+        serialize_u128 u128: tag::U128;
+        serialize_f32 f32: tag::F32;
+        serialize_f64 f64: tag::F64;
+    }
+
+    cell_rollback! {
+        serialize_char(char);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    #[inline]
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.column.set_tag(tag::STR)?;
+        self.column.values.write_buf(v.as_bytes());
+        self.column.push_offset()
+    }
+
+    #[inline]
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        self.column.set_tag(tag::BYTES)?;
+        self.column.values.write_buf(v);
+        self.column.push_offset()
+    }
+
+    #[inline]
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(DataError::RollBack)
+    }
+
+    #[inline]
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if Adapter::from_name(name).is_some() {
+            return Err(DataError::RollBack);
+        }
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(DataError::RollBack)
+    }
+
+    #[inline]
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(DataError::RollBack)
+    }
+
+    #[inline]
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(DataError::RollBack)
+    }
+
+    #[inline]
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(DataError::RollBack)
+    }
+
+    #[inline]
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(DataError::RollBack)
+    }
+
+    #[inline]
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(DataError::RollBack)
+    }
+
+    #[inline]
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(DataError::RollBack)
+    }
+
+    #[inline]
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(DataError::RollBack)
+    }
+}
+
+impl Data {
+    /// write `rows` as one column per field
+    #[inline]
+    pub fn write_columnar<T: Serialize>(&mut self, rows: &[T]) -> Result<(), DataError> {
+        let mut columns = Columns {
+            columns: Vec::new(),
+            rows: 0,
+            field: 0,
+            config: self.config,
+        };
+        for row in rows {
+            row.serialize(&mut columns)?;
+        }
+        self.write_len(rows.len())?;
+        self.write_len(columns.columns.len())?;
+        for column in columns.columns {
+            self.try_write_fixed(column.name)?;
+            let tag = column.tag.unwrap_or(VALUE);
+            self.write_fixed(tag);
+            if fixed_width(tag).is_none() {
+                for offset in column.offsets {
+                    self.write_fixed(offset);
+                }
+            }
+            self.write_buf(&column.values);
+        }
+        Ok(())
+    }
+}
+
+/// a column being read
+struct Column<'de> {
+    name: &'de str,
+    tag: u8,
+    offsets: &'de [u8],
+    values: &'de [u8],
+}
+
+impl<'de> Column<'de> {
+    #[inline]
+    fn offset(&self, row: usize) -> Result<usize, DataError> {
+        Ok(DataReader::from(&self.offsets[row * 4..row * 4 + 4]).read_fixed::<u32>()? as usize)
+    }
+
+    /// the deserializer of the value in `row`
+    #[inline]
+    fn cell(&self, row: usize, config: Config) -> Result<Cell<'de>, DataError> {
+        let bytes = match fixed_width(self.tag) {
+            Some(width) => &self.values[row * width..(row + 1) * width],
+            None => {
+                let start = if row == 0 { 0 } else { self.offset(row - 1)? };
+                let end = self.offset(row)?;
+                if start > end || end > self.values.len() {
+                    return Err(anyhow!("column {} has bad offsets", self.name).into());
+                }
+                &self.values[start..end]
+            }
+        };
+        Ok(Cell {
+            tag: self.tag,
+            bytes,
+            config,
+        })
+    }
+}
+
+impl<'a> DataReader<'a> {
+    /// read the row count and the columns of a columnar block
+    fn read_columns(&mut self) -> Result<(usize, Vec<Column<'a>>), DataError> {
+        let rows = self.read_len()?;
+        let count = self.read_len()?;
+        let mut columns = Vec::with_capacity(count.min(self.len()));
+        for _ in 0..count {
+            let name = self.read_fixed_str()?;
+            let tag = self.read_fixed::<u8>()?;
+            let column = match fixed_width(tag) {
+                Some(width) => {
+                    let size = rows
+                        .checked_mul(width)
+                        .ok_or_else(|| anyhow!("column {} too large", name))?;
+                    Column {
+                        name,
+                        tag,
+                        offsets: &[],
+                        values: self.take_buf(size)?,
+                    }
+                }
+                None => {
+                    let size = rows
+                        .checked_mul(4)
+                        .ok_or_else(|| anyhow!("column {} too large", name))?;
+                    let offsets = self.take_buf(size)?;
+                    let len = match offsets.len().checked_sub(4) {
+                        Some(last) => DataReader::from(&offsets[last..]).read_fixed::<u32>()?,
+                        None => 0,
+                    };
+                    Column {
+                        name,
+                        tag,
+                        offsets,
+                        values: self.take_buf(len as usize)?,
+                    }
+                }
+            };
+            columns.push(column);
+        }
+        Ok((rows, columns))
+    }
+
+    /// read the rows of a block written by `write_columnar`
+    #[inline]
+    pub fn read_columnar<T: Deserialize<'a>>(&mut self) -> Result<Vec<T>, DataError> {
+        let (rows, columns) = self.read_columns()?;
+        (0..rows)
+            .map(|row| {
+                T::deserialize(Row {
+                    columns: &columns,
+                    row,
+                    config: self.config,
+                })
+            })
+            .collect()
+    }
+
+    /// read the values of one column of a block written by `write_columnar`,
+    /// the other columns are skipped
+    #[inline]
+    pub fn read_column<T: Deserialize<'a>>(&mut self, name: &str) -> Result<Vec<T>, DataError> {
+        let (rows, columns) = self.read_columns()?;
+        let column = match columns.iter().find(|column| column.name == name) {
+            Some(column) => column,
+            None => return Err(anyhow!("no column {}", name).into()),
+        };
+        (0..rows)
+            .map(|row| T::deserialize(column.cell(row, self.config)?))
+            .collect()
+    }
+}
+
+/// Deserializes a row as a map from the column names to its values.
+struct Row<'c, 'de> {
+    columns: &'c [Column<'de>],
+    row: usize,
+    config: Config,
+}
+
+impl<'c, 'de> Deserializer<'de> for Row<'c, 'de> {
+    type Error = DataError;
+
+    #[inline]
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(RowAccess { row: self, next: 0 })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct RowAccess<'c, 'de> {
+    row: Row<'c, 'de>,
+    next: usize,
+}
+
+impl<'c, 'de> MapAccess<'de> for RowAccess<'c, 'de> {
+    type Error = DataError;
+
+    #[inline]
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.row.columns.get(self.next) {
+            Some(column) => seed
+                .deserialize(BorrowedStrDeserializer::new(column.name))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    #[inline]
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let column = &self.row.columns[self.next];
+        self.next += 1;
+        seed.deserialize(column.cell(self.row.row, self.row.config)?)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.row.columns.len() - self.next)
+    }
+}
+
+/// Deserializes one value of a column.
+struct Cell<'de> {
+    tag: u8,
+    bytes: &'de [u8],
+    config: Config,
+}
+
+impl<'de> Cell<'de> {
+    #[inline]
+    fn reader(&self) -> DataReader<'de> {
+        let mut reader = DataReader::from(self.bytes);
+        reader.config = self.config;
+        reader
+    }
+}
+
+macro_rules! forward_cell {
+    ($($method:ident($($arg:ident: $type:ty),*);)+) => {
+        $(
+        #[inline]
+        fn $method<V>(self, $($arg: $type,)* visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            if self.tag == VALUE {
+                Deserializer::$method(&mut self.reader(), $($arg,)* visitor)
+            } else {
+                self.deserialize_any(visitor)
+            }
+        }
+        )+
+    };
+}
+
+impl<'de> Deserializer<'de> for Cell<'de> {
+    type Error = DataError;
+
+    #[inline]
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let mut rd = self.reader();
+        match self.tag {
+            tag::TRUE => visitor.visit_bool(rd.read_fixed()?),
+            tag::I8 => visitor.visit_i8(rd.read_fixed()?),
+            tag::I16 => visitor.visit_i16(rd.read_fixed()?),
+            tag::I32 => visitor.visit_i32(rd.read_fixed()?),
+            tag::I64 => visitor.visit_i64(rd.read_fixed()?),
+            tag::I128 => visitor.visit_i128(rd.read_fixed()?),
+            tag::U8 => visitor.visit_u8(rd.read_fixed()?),
+            tag::U16 => visitor.visit_u16(rd.read_fixed()?),
+            tag::U32 => visitor.visit_u32(rd.read_fixed()?),
+            tag::U64 => visitor.visit_u64(rd.read_fixed()?),
+            tag::U128 => visitor.visit_u128(rd.read_fixed()?),
+            tag::F32 => visitor.visit_f32(rd.read_fixed()?),
+            tag::F64 => visitor.visit_f64(rd.read_fixed()?),
+            tag::STR => match std::str::from_utf8(self.bytes) {
+                Ok(v) => visitor.visit_borrowed_str(v),
+                Err(err) => Err(anyhow!("column string: {}", err).into()),
+            },
+            tag::BYTES => visitor.visit_borrowed_bytes(self.bytes),
+            VALUE => Deserializer::deserialize_any(&mut rd, visitor),
+            tag => Err(anyhow!("unknown column type tag {}", tag).into()),
+        }
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.tag == VALUE {
+            Deserializer::deserialize_newtype_struct(&mut self.reader(), name, visitor)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    #[inline]
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_cell! {
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_option();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        self.config.human_readable
+    }
+}
+
+/// Write a `Vec` of structs as a columnar block, for `#[serde(with = "data_rw::columnar")]`.
+/// The block is written as bytes with the default config and its strings
+/// are borrowed when read, so the deserializer must lend its bytes like `DataReader`.
+#[inline]
+pub fn serialize<T, S>(rows: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    let mut data = Data::new();
+    data.write_columnar(rows)
+        .map_err(serde::ser::Error::custom)?;
+    serializer.serialize_bytes(&data)
+}
+
+#[inline]
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_bytes(ColumnarVisitor(PhantomData))
+}
+
+struct ColumnarVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for ColumnarVisitor<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("borrowed columnar bytes")
+    }
+
+    #[inline]
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        DataReader::from(v).read_columnar().map_err(E::custom)
+    }
+}
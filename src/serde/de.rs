@@ -1,8 +1,14 @@
+use crate::config::Profile;
+use crate::data_read::{ReadNumberFixed, ReadNumberVar};
+use crate::dotnet::NetNumber;
+use crate::java::JavaNumber;
+use crate::serde::adapter::Adapter;
 use crate::serde::error::DataError;
-use crate::DataReader;
-use anyhow::{anyhow, Result};
+use crate::serde::tag;
+use crate::{DataReader, IntEncoding};
+use anyhow::{anyhow, bail, ensure, Result};
 use paste::paste;
-use serde::de::{DeserializeSeed, Visitor};
+use serde::de::{DeserializeSeed, IntoDeserializer, Unexpected, Visitor};
 use serde::Deserializer;
 
 macro_rules! make_deserialize {
@@ -11,35 +17,151 @@ macro_rules! make_deserialize {
              #[inline]
              fn [<deserialize_ $t>]<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value, Self::Error> where
                 V: Visitor<'de> {
-                visitor.[<visit_ $t>]( self.read_fixed::<$t>()?)
+                if self.config.tagged {
+                    return self.deserialize_any(visitor);
+                }
+                visitor.[<visit_ $t>]( self.read_number::<$t>()?)
              }
          }
     };
 }
 
-impl<'de, 'a, 'b> Deserializer<'de> for &'a mut DataReader<'b>
+macro_rules! make_int_deserialize {
+    ($t:ty) => {
+         paste!{
+             #[inline]
+             fn [<deserialize_ $t>]<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value, Self::Error> where
+                V: Visitor<'de> {
+                if self.config.tagged {
+                    return self.deserialize_any(visitor);
+                }
+                visitor.[<visit_ $t>]( self.read_int::<$t>()?)
+             }
+         }
+    };
+}
+
+impl<'a> DataReader<'a> {
+    /// read an integer written in the config's `IntEncoding`
+    #[inline]
+    fn read_int<T: ReadNumberFixed + ReadNumberVar + JavaNumber + NetNumber>(
+        &mut self,
+    ) -> Result<T> {
+        if self.config.int_encoding == IntEncoding::Varint || self.adapter == Adapter::Varint {
+            self.read_var_integer()
+        } else {
+            self.read_number()
+        }
+    }
+
+    /// read a fixed size number, big endian for the Java profile
+    /// and little endian for the bincode and .NET ones
+    #[inline]
+    fn read_number<T: ReadNumberFixed + JavaNumber + NetNumber>(&mut self) -> Result<T> {
+        match self.config.profile {
+            Profile::Java => self.read_java_fixed(),
+            _ if self.config.little_endian() => self.read_net_fixed(),
+            _ => self.read_fixed(),
+        }
+    }
+
+    /// read a char written as its utf8 bytes without a length
+    #[inline]
+    pub(crate) fn read_utf8_char(&mut self) -> Result<char> {
+        let first = *self
+            .first()
+            .ok_or_else(|| anyhow!("read char: buff is empty"))?;
+        let width = match first {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => bail!("read char: invalid utf8 first byte:{}", first),
+        };
+        ensure!(width <= self.len(), "read char: need {} bytes", width);
+        let (res, have) = self.buff.split_at(width);
+        self.buff = have;
+        let str = std::str::from_utf8(res)?;
+        Ok(str.chars().next().unwrap_or_default())
+    }
+
+    /// read a str with the length prefix of the config or the field adapter
+    #[inline]
+    fn read_str(&mut self) -> Result<&'a str> {
+        if self.adapter == Adapter::U16LenStr {
+            let len = self.read_fixed::<u16>()?;
+            self.take_str(len as usize)
+        } else {
+            self.read_fixed_str()
+        }
+    }
+}
+
+impl<'de, 'b> Deserializer<'de> for &mut DataReader<'b>
 where
     'b: 'de,
 {
     type Error = DataError;
 
     #[inline]
-    fn deserialize_any<V>(self, _visitor: V) -> Result<<V as Visitor<'de>>::Value, Self::Error>
+    fn deserialize_any<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(DataError::AnyNotSupported)
+        if !self.config.tagged {
+            return Err(DataError::AnyNotSupported);
+        }
+        let tag = self.read_fixed::<u8>()?;
+        if self.mode == 1 && !(tag::FALSE..=tag::F64).contains(&tag) {
+            return Err(DataError::Reset);
+        }
+        match tag {
+            tag::NONE => visitor.visit_none(),
+            tag::SOME => visitor.visit_some(self),
+            tag::UNIT => visitor.visit_unit(),
+            tag::FALSE => visitor.visit_bool(false),
+            tag::TRUE => visitor.visit_bool(true),
+            tag::I8 => visitor.visit_i8(self.read_number()?),
+            tag::I16 => visitor.visit_i16(self.read_int()?),
+            tag::I32 => visitor.visit_i32(self.read_int()?),
+            tag::I64 => visitor.visit_i64(self.read_int()?),
+            tag::I128 => visitor.visit_i128(self.read_number()?),
+            tag::U8 => visitor.visit_u8(self.read_number()?),
+            tag::U16 => visitor.visit_u16(self.read_int()?),
+            tag::U32 => visitor.visit_u32(self.read_int()?),
+            tag::U64 => visitor.visit_u64(self.read_int()?),
+            tag::U128 => visitor.visit_u128(self.read_number()?),
+            tag::F32 => visitor.visit_f32(self.read_number()?),
+            tag::F64 => visitor.visit_f64(self.read_number()?),
+            tag::CHAR => {
+                let str = self.read_fixed_str()?;
+                let mut chars = str.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => visitor.visit_char(c),
+                    _ => Err(anyhow!("deserialize_any: invalid char:{}", str).into()),
+                }
+            }
+            tag::STR if self.config.profile == Profile::Java => {
+                visitor.visit_string(self.read_java_utf()?)
+            }
+            tag::STR => visitor.visit_borrowed_str(self.read_fixed_str()?),
+            tag::BYTES => visitor.visit_borrowed_bytes(self.read_fixed_buf()?),
+            tag::SEQ => visitor.visit_seq(SeqAssess::new(self)?),
+            tag::MAP => visitor.visit_map(MapAccess::new(self, false)?),
+            tag::VARIANT => visitor.visit_u32(self.read_var_integer()?),
+            _ => Err(anyhow!("deserialize_any: unknown type tag:{}", tag).into()),
+        }
     }
 
     make_deserialize!(bool);
     make_deserialize!(i8);
     make_deserialize!(u8);
-    make_deserialize!(i16);
-    make_deserialize!(u16);
-    make_deserialize!(i32);
-    make_deserialize!(u32);
-    make_deserialize!(i64);
-    make_deserialize!(u64);
+    make_int_deserialize!(i16);
+    make_int_deserialize!(u16);
+    make_int_deserialize!(i32);
+    make_int_deserialize!(u32);
+    make_int_deserialize!(i64);
+    make_int_deserialize!(u64);
     make_deserialize!(i128);
     make_deserialize!(u128);
     make_deserialize!(f32);
@@ -50,6 +172,18 @@ where
     where
         V: Visitor<'de>,
     {
+        if self.config.tagged {
+            return self.deserialize_any(visitor);
+        }
+        if self.config.profile == Profile::DotNet {
+            return visitor.visit_char(self.read_net_char()?);
+        }
+        if self.config.profile == Profile::Java {
+            return visitor.visit_char(self.read_java_char()?);
+        }
+        if self.config.foreign() {
+            return visitor.visit_char(self.read_utf8_char()?);
+        }
         self.deserialize_str(visitor)
     }
 
@@ -61,7 +195,13 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
-        let str = self.read_fixed_str()?;
+        if self.config.tagged {
+            return self.deserialize_any(visitor);
+        }
+        if self.config.profile == Profile::Java {
+            return visitor.visit_string(self.read_java_utf()?);
+        }
+        let str = self.read_str()?;
         visitor.visit_borrowed_str(str)
     }
 
@@ -81,6 +221,9 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
+        if self.config.tagged {
+            return self.deserialize_any(visitor);
+        }
 
         let buff = self.read_fixed_buf()?;
         visitor.visit_borrowed_bytes(buff)
@@ -102,6 +245,9 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
+        if self.config.tagged {
+            return self.deserialize_any(visitor);
+        }
 
         if self.read_fixed::<u8>()? == 0 {
             visitor.visit_none()
@@ -118,8 +264,11 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
+        if self.config.tagged {
+            return self.deserialize_any(visitor);
+        }
 
-        if self.read_fixed::<u8>()? == 0 {
+        if !self.config.foreign() && self.read_fixed::<u8>()? == 0 {
             return Err(anyhow!("deserialize_unit: current data !=0u8").into());
         }
 
@@ -144,7 +293,7 @@ where
     #[inline]
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<<V as Visitor<'de>>::Value, Self::Error>
     where
@@ -153,6 +302,14 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
+        if !self.config.tagged {
+            if let Some(adapter) = Adapter::from_name(name) {
+                let outer = std::mem::replace(&mut self.adapter, adapter);
+                let r = visitor.visit_newtype_struct(&mut *self);
+                self.adapter = outer;
+                return r;
+            }
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -164,13 +321,16 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
+        if self.config.tagged {
+            return self.deserialize_any(visitor);
+        }
         visitor.visit_seq(SeqAssess::new(self)?)
     }
 
     #[inline]
     fn deserialize_tuple<V>(
         self,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<<V as Visitor<'de>>::Value, Self::Error>
     where
@@ -179,6 +339,13 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
+        if self.adapter == Adapter::FixedArray {
+            self.adapter = Adapter::None;
+            return visitor.visit_seq(SeqAssess::with_len(self, len));
+        }
+        if !self.config.tuple_len() {
+            return visitor.visit_seq(SeqAssess::with_len(self, len));
+        }
         self.deserialize_seq(visitor)
     }
 
@@ -186,7 +353,7 @@ where
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<<V as Visitor<'de>>::Value, Self::Error>
     where
@@ -195,7 +362,7 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
-        self.deserialize_seq(visitor)
+        self.deserialize_tuple(len, visitor)
     }
 
     #[inline]
@@ -206,14 +373,17 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
-        visitor.visit_map(MapAccess::new(self)?)
+        if self.config.tagged {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_map(MapAccess::new(self, false)?)
     }
 
     #[inline]
     fn deserialize_struct<V>(
         self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<<V as Visitor<'de>>::Value, Self::Error>
     where
@@ -222,7 +392,14 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
-        visitor.visit_map(MapAccess::new(self)?)
+        if self.config.tagged {
+            return self.deserialize_any(visitor);
+        }
+        if self.config.positional_structs {
+            return visitor.visit_seq(SeqAssess::with_len(self, fields.len()));
+        }
+        let framed = self.config.skippable_fields;
+        visitor.visit_map(MapAccess::new(self, framed)?)
     }
 
     #[inline]
@@ -238,7 +415,19 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
-        visitor.visit_enum(VariantAccess::new(self)?)
+        if self.config.tagged {
+            // unit variants are written as the bare variant,
+            // all others as a single entry map from the variant to the content
+            if self.first() != Some(&tag::MAP) {
+                return visitor.visit_enum(VariantAccess::new(self, true)?);
+            }
+            self.advance(1)?;
+            let len = self.read_len()?;
+            if len != 1 {
+                return Err(anyhow!("deserialize_enum: map len {} != 1", len).into());
+            }
+        }
+        visitor.visit_enum(VariantAccess::new(self, false)?)
     }
 
     #[inline]
@@ -252,24 +441,36 @@ where
         if self.mode == 1 {
             return Err(DataError::Reset);
         }
-        self.deserialize_str(visitor)
+        if self.config.tagged {
+            return self.deserialize_any(visitor);
+        }
+        // field and variant names are never written by a field adapter
+        visitor.visit_borrowed_str(self.read_fixed_str()?)
     }
 
     #[inline]
     fn deserialize_ignored_any<V>(
         self,
-        _visitor: V,
+        visitor: V,
     ) -> Result<<V as Visitor<'de>>::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        if self.config.tagged {
+            return self.deserialize_any(visitor);
+        }
         Err(DataError::IgnoredAnyNotSupported)
     }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        self.config.human_readable
+    }
 }
 
 struct SeqAssess<'a, 'b> {
-    len: u32,
-    current: u32,
+    len: usize,
+    current: usize,
     data: &'a mut DataReader<'b>,
 }
 
@@ -280,12 +481,18 @@ impl<'a, 'b> SeqAssess<'a, 'b> {
             return Err(DataError::Reset);
         }
 
-        let len = data.read_fixed::<u32>()?;
-        Ok(SeqAssess {
+        let len = data.read_len()?;
+        Ok(SeqAssess::with_len(data, len))
+    }
+
+    /// the length is known by the reader and was not written
+    #[inline]
+    pub fn with_len(data: &'a mut DataReader<'b>, len: usize) -> Self {
+        SeqAssess {
             len,
             current: 0,
             data,
-        })
+        }
     }
 }
 
@@ -304,22 +511,29 @@ impl<'a, 'de, 'b: 'de> serde::de::SeqAccess<'de> for SeqAssess<'a, 'b> {
             Ok(None)
         }
     }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.len - self.current).min(self.data.len()))
+    }
 }
 
 struct MapAccess<'a, 'b> {
     data: &'a mut DataReader<'b>,
-    len: u32,
-    current: u32,
+    len: usize,
+    current: usize,
+    framed: bool,
 }
 
 impl<'a, 'b> MapAccess<'a, 'b> {
     #[inline]
-    pub fn new(data: &'a mut DataReader<'b>) -> Result<Self, DataError> {
-        let len = data.read_fixed::<u32>()?;
+    pub fn new(data: &'a mut DataReader<'b>, framed: bool) -> Result<Self, DataError> {
+        let len = data.read_len()?;
         Ok(MapAccess {
             data,
             len,
             current: 0,
+            framed,
         })
     }
 }
@@ -352,18 +566,127 @@ impl<'de, 'a, 'b: 'de> serde::de::MapAccess<'de> for MapAccess<'a, 'b> {
     where
         V: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.data)
+        if self.framed {
+            let len = self.data.read_len()?;
+            if len > self.data.len() {
+                return Err(anyhow!("read field size too big,{}>{}", len, self.data.len()).into());
+            }
+            let mut field = DataReader {
+                buff: &self.data.buff[..len],
+                original_len: len,
+                mode: self.data.mode,
+                config: self.data.config,
+                adapter: self.data.adapter,
+            };
+            let r = seed.deserialize(FieldValue { data: &mut field })?;
+            self.data.advance(len)?;
+            Ok(r)
+        } else {
+            seed.deserialize(&mut *self.data)
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.len - self.current).min(self.data.len()))
+    }
+}
+
+/// The value of a length-prefixed field. Ignoring it skips the whole field,
+/// the values nested in it are read by the plain `DataReader`.
+struct FieldValue<'a, 'b> {
+    data: &'a mut DataReader<'b>,
+}
+
+macro_rules! forward_field_value {
+    ($($method:ident($($arg:ident: $type:ty),*);)+) => {
+        $(
+        #[inline]
+        fn $method<V>(self, $($arg: $type,)* visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.data.$method($($arg,)* visitor)
+        }
+        )+
+    };
+}
+
+impl<'de, 'a, 'b: 'de> Deserializer<'de> for FieldValue<'a, 'b> {
+    type Error = DataError;
+
+    forward_field_value! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_option();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_newtype_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+    }
+
+    #[inline]
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.data.config.tagged {
+            return self.data.deserialize_ignored_any(visitor);
+        }
+        self.data.buff = &self.data.buff[self.data.buff.len()..];
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        self.data.is_human_readable()
     }
 }
 
 struct VariantAccess<'a, 'b> {
     data: &'a mut DataReader<'b>,
+    /// only the variant was written, there is no content
+    bare: bool,
 }
 
 impl<'a, 'b> VariantAccess<'a, 'b> {
     #[inline]
-    pub fn new(data: &'a mut DataReader<'b>) -> Result<Self, DataError> {
-        Ok(VariantAccess { data })
+    pub fn new(data: &'a mut DataReader<'b>, bare: bool) -> Result<Self, DataError> {
+        Ok(VariantAccess { data, bare })
+    }
+
+    #[inline]
+    fn check_content(&self) -> Result<(), DataError> {
+        if self.bare {
+            return Err(serde::de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"variant with content",
+            ));
+        }
+        Ok(())
     }
 }
 
@@ -378,6 +701,24 @@ impl<'de, 'a, 'b: 'de> serde::de::EnumAccess<'de> for VariantAccess<'a, 'b> {
     where
         V: DeserializeSeed<'de>,
     {
+        if self.data.config.foreign() {
+            let index = self.data.read_number::<u32>()?;
+            let value =
+                seed.deserialize(IntoDeserializer::<DataError>::into_deserializer(index))?;
+            return Ok((value, self));
+        }
+        if self.data.config.variant_index {
+            if self.data.config.tagged {
+                let tag = self.data.read_fixed::<u8>()?;
+                if tag != tag::VARIANT {
+                    return Err(anyhow!("variant_seed: type tag {} is not variant", tag).into());
+                }
+            }
+            let index = self.data.read_var_integer::<u32>()?;
+            let value =
+                seed.deserialize(IntoDeserializer::<DataError>::into_deserializer(index))?;
+            return Ok((value, self));
+        }
         Ok((seed.deserialize(&mut *self.data)?, self))
     }
 }
@@ -387,7 +728,10 @@ impl<'de, 'a, 'b: 'de> serde::de::VariantAccess<'de> for VariantAccess<'a, 'b> {
 
     #[inline]
     fn unit_variant(self) -> Result<(), Self::Error> {
-        serde::de::Deserialize::deserialize(self.data)
+        if self.data.config.tagged && !self.bare {
+            return serde::de::Deserialize::deserialize(self.data);
+        }
+        Ok(())
     }
 
     #[inline]
@@ -398,19 +742,21 @@ impl<'de, 'a, 'b: 'de> serde::de::VariantAccess<'de> for VariantAccess<'a, 'b> {
     where
         T: DeserializeSeed<'de>,
     {
+        self.check_content()?;
         seed.deserialize(self.data)
     }
 
     #[inline]
     fn tuple_variant<V>(
         self,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<<V as Visitor<'de>>::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        serde::de::Deserializer::deserialize_seq(self.data, visitor)
+        self.check_content()?;
+        serde::de::Deserializer::deserialize_tuple(self.data, len, visitor)
     }
 
     #[inline]
@@ -422,6 +768,7 @@ impl<'de, 'a, 'b: 'de> serde::de::VariantAccess<'de> for VariantAccess<'a, 'b> {
     where
         V: Visitor<'de>,
     {
+        self.check_content()?;
         serde::de::Deserializer::deserialize_struct(self.data, "", fields, visitor)
     }
 }
@@ -0,0 +1,813 @@
+//! Order-preserving key encoding, comparing two keys byte by byte
+//! gives the same order as `Ord` on their values.
+//!
+//! - integers are big endian, signed ones with the sign bit flipped
+//! - floats sort like `total_cmp`, `-0.0` before `0.0` and NaN after infinity
+//! - strings and bytes escape `0x00` as `0x00 0xff` and end with `0x00 0x01`
+//! - sequences and maps write `1` before every element and end with `0`
+//! - options write `0` for none and `1` before some
+//! - tuples and structs are their fields in order, enums a big endian u32 variant index
+//!
+//! A descending key is the ascending one with every byte inverted.
+//! Strings can't be borrowed from the key, deserialize them as `String`.
+
+use crate::serde::error::DataError;
+use crate::{Data, DataReader};
+use anyhow::anyhow;
+use paste::paste;
+use serde::de::{DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{
+    SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::{Deserialize, Serialize, Serializer};
+
+impl Data {
+    /// serialize an ascending key
+    #[inline]
+    pub fn serialize_key<T: Serialize>(value: T) -> Result<Self, DataError> {
+        let mut data = Data::new();
+        data.write_key(value, false)?;
+        Ok(data)
+    }
+
+    /// append a key, descending keys sort in reverse order,
+    /// so keys can mix ascending and descending parts
+    #[inline]
+    pub fn write_key<T: Serialize>(&mut self, value: T, descending: bool) -> Result<(), DataError> {
+        value.serialize(&mut KeySerializer {
+            data: self,
+            descending,
+        })
+    }
+}
+
+impl<'de> DataReader<'de> {
+    /// deserialize an ascending key
+    #[inline]
+    pub fn deserialize_key<D: Deserialize<'de>, T: AsRef<[u8]>>(v: &'de T) -> Result<D, DataError> {
+        DataReader::from(v.as_ref()).read_key(false)
+    }
+
+    /// read a key written by `write_key` with the same `descending`
+    #[inline]
+    pub fn read_key<D: Deserialize<'de>>(&mut self, descending: bool) -> Result<D, DataError> {
+        D::deserialize(&mut KeyDeserializer {
+            reader: self,
+            descending,
+        })
+    }
+}
+
+/// Serializer of the key encoding.
+pub struct KeySerializer<'a> {
+    data: &'a mut Data,
+    descending: bool,
+}
+
+impl<'a> KeySerializer<'a> {
+    #[inline]
+    fn put(&mut self, buf: &[u8]) {
+        let start = self.data.len();
+        self.data.write_buf(buf);
+        if self.descending {
+            for b in &mut self.data[start..] {
+                *b = !*b;
+            }
+        }
+    }
+
+    #[inline]
+    fn put_escaped(&mut self, v: &[u8]) {
+        for (i, part) in v.split(|&b| b == 0).enumerate() {
+            if i > 0 {
+                self.put(&[0, 0xff]);
+            }
+            self.put(part);
+        }
+        self.put(&[0, 1]);
+    }
+}
+
+macro_rules! make_key_int_serialize {
+    ($($type:ty)+) => {
+      paste!{
+        $(
+        #[inline]
+        fn [<serialize_ $type>] (self, v: $type) -> Result<Self::Ok, Self::Error> {
+            self.put(&(v ^ <$type>::MIN).to_be_bytes());
+            Ok(())
+        }
+        )*
+      }
+    };
+}
+
+impl<'a, 'b> Serializer for &'a mut KeySerializer<'b> {
+    type Ok = ();
+    type Error = DataError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    make_key_int_serialize!(i8 u8 i16 u16 i32 u32 i64 u64 i128 u128);
+
+    #[inline]
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.put(&[v as u8]);
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        let bits = v.to_bits();
+        let bits = if bits >> 31 == 1 {
+            !bits
+        } else {
+            bits | 1 << 31
+        };
+        self.put(&bits.to_be_bytes());
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        let bits = v.to_bits();
+        let bits = if bits >> 63 == 1 {
+            !bits
+        } else {
+            bits | 1 << 63
+        };
+        self.put(&bits.to_be_bytes());
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.put(&(v as u32).to_be_bytes());
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.put_escaped(v.as_bytes());
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        self.put_escaped(v);
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        self.put(&[0]);
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.put(&[1]);
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.put(&variant_index.to_be_bytes());
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.put(&variant_index.to_be_bytes());
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        self.put(&variant_index.to_be_bytes());
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        self.put(&variant_index.to_be_bytes());
+        Ok(self)
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<'a, 'b> SerializeSeq for &'a mut KeySerializer<'b> {
+    type Ok = ();
+    type Error = DataError;
+
+    #[inline]
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.put(&[1]);
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.put(&[0]);
+        Ok(())
+    }
+}
+
+impl<'a, 'b> SerializeMap for &'a mut KeySerializer<'b> {
+    type Ok = ();
+    type Error = DataError;
+
+    #[inline]
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.put(&[1]);
+        key.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.put(&[0]);
+        Ok(())
+    }
+}
+
+macro_rules! make_key_fields_serialize {
+    ($($trait:ident $method:ident $($key:ident)?;)+) => {
+        $(
+        impl<'a, 'b> $trait for &'a mut KeySerializer<'b> {
+            type Ok = ();
+            type Error = DataError;
+
+            #[inline]
+            fn $method<T>(&mut self, $($key: &'static str,)? value: &T) -> Result<(), Self::Error>
+            where
+                T: ?Sized + Serialize,
+            {
+                value.serialize(&mut **self)
+            }
+
+            $(
+            /// fields are read back by position, a skipped field would shift the rest
+            #[inline]
+            fn skip_field(&mut self, $key: &'static str) -> Result<(), Self::Error> {
+                Err(anyhow!("key can't skip field {}", $key).into())
+            }
+            )?
+
+            #[inline]
+            fn end(self) -> Result<Self::Ok, Self::Error> {
+                Ok(())
+            }
+        }
+        )*
+    };
+}
+
+make_key_fields_serialize! {
+    SerializeTuple serialize_element;
+    SerializeTupleStruct serialize_field;
+    SerializeTupleVariant serialize_field;
+    SerializeStruct serialize_field _key;
+    SerializeStructVariant serialize_field _key;
+}
+
+/// Deserializer of the key encoding.
+pub struct KeyDeserializer<'a, 'de> {
+    reader: &'a mut DataReader<'de>,
+    descending: bool,
+}
+
+impl<'a, 'de> KeyDeserializer<'a, 'de> {
+    #[inline]
+    fn take(&mut self, buf: &mut [u8]) -> Result<(), DataError> {
+        self.reader.read_buff(buf)?;
+        if self.descending {
+            for b in buf.iter_mut() {
+                *b = !*b;
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn take_u8(&mut self) -> Result<u8, DataError> {
+        let mut buf = [0];
+        self.take(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    #[inline]
+    fn take_u32(&mut self) -> Result<u32, DataError> {
+        let mut buf = [0; 4];
+        self.take(&mut buf)?;
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    #[inline]
+    fn take_escaped(&mut self) -> Result<Vec<u8>, DataError> {
+        let mut res = Vec::new();
+        loop {
+            match self.take_u8()? {
+                0 => match self.take_u8()? {
+                    0xff => res.push(0),
+                    1 => return Ok(res),
+                    b => return Err(anyhow!("key: invalid escape 0x00 {:#x}", b).into()),
+                },
+                b => res.push(b),
+            }
+        }
+    }
+
+    /// the marker before each element of a sequence or map, false at its end
+    #[inline]
+    fn more(&mut self) -> Result<bool, DataError> {
+        match self.take_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(anyhow!("key: invalid element marker {}", b).into()),
+        }
+    }
+}
+
+macro_rules! make_key_int_deserialize {
+    ($($type:ty)+) => {
+      paste!{
+        $(
+        #[inline]
+        fn [<deserialize_ $type>]<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            let mut buf = [0; std::mem::size_of::<$type>()];
+            self.take(&mut buf)?;
+            visitor.[<visit_ $type>](<$type>::from_be_bytes(buf) ^ <$type>::MIN)
+        }
+        )*
+      }
+    };
+}
+
+impl<'a, 'b, 'de> serde::Deserializer<'de> for &'a mut KeyDeserializer<'b, 'de> {
+    type Error = DataError;
+
+    make_key_int_deserialize!(i8 u8 i16 u16 i32 u32 i64 u64 i128 u128);
+
+    #[inline]
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(DataError::AnyNotSupported)
+    }
+
+    #[inline]
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.take_u8()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            b => Err(anyhow!("key: invalid bool {}", b).into()),
+        }
+    }
+
+    #[inline]
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.take_u32()?;
+        let bits = if bits >> 31 == 1 {
+            bits & !(1 << 31)
+        } else {
+            !bits
+        };
+        visitor.visit_f32(f32::from_bits(bits))
+    }
+
+    #[inline]
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let mut buf = [0; 8];
+        self.take(&mut buf)?;
+        let bits = u64::from_be_bytes(buf);
+        let bits = if bits >> 63 == 1 {
+            bits & !(1 << 63)
+        } else {
+            !bits
+        };
+        visitor.visit_f64(f64::from_bits(bits))
+    }
+
+    #[inline]
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let v = self.take_u32()?;
+        match char::from_u32(v) {
+            Some(c) => visitor.visit_char(c),
+            None => Err(anyhow!("key: invalid char {:#x}", v).into()),
+        }
+    }
+
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let buf = self.take_escaped()?;
+        match String::from_utf8(buf) {
+            Ok(v) => visitor.visit_string(v),
+            Err(err) => Err(anyhow!("key: {}", err).into()),
+        }
+    }
+
+    #[inline]
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    #[inline]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_byte_buf(self.take_escaped()?)
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.take_u8()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            b => Err(anyhow!("key: invalid option {}", b).into()),
+        }
+    }
+
+    #[inline]
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    #[inline]
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(KeyAccess {
+            de: self,
+            len: None,
+        })
+    }
+
+    #[inline]
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(KeyAccess {
+            de: self,
+            len: Some(len),
+        })
+    }
+
+    #[inline]
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    #[inline]
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(KeyAccess {
+            de: self,
+            len: None,
+        })
+    }
+
+    #[inline]
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    #[inline]
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(self)
+    }
+
+    #[inline]
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u32(self.take_u32()?)
+    }
+
+    #[inline]
+    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(DataError::IgnoredAnyNotSupported)
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Elements of a sequence or map, fields of a tuple or struct when `len` is known.
+struct KeyAccess<'r, 'a, 'de> {
+    de: &'r mut KeyDeserializer<'a, 'de>,
+    len: Option<usize>,
+}
+
+impl<'r, 'a, 'de> KeyAccess<'r, 'a, 'de> {
+    #[inline]
+    fn next(&mut self) -> Result<bool, DataError> {
+        match self.len.as_mut() {
+            Some(0) => Ok(false),
+            Some(len) => {
+                *len -= 1;
+                Ok(true)
+            }
+            None => self.de.more(),
+        }
+    }
+}
+
+impl<'r, 'a, 'de> serde::de::SeqAccess<'de> for KeyAccess<'r, 'a, 'de> {
+    type Error = DataError;
+
+    #[inline]
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.next()? {
+            Ok(Some(seed.deserialize(&mut *self.de)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        self.len
+    }
+}
+
+impl<'r, 'a, 'de> serde::de::MapAccess<'de> for KeyAccess<'r, 'a, 'de> {
+    type Error = DataError;
+
+    #[inline]
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.next()? {
+            Ok(Some(seed.deserialize(&mut *self.de)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[inline]
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+impl<'a, 'b, 'de> serde::de::EnumAccess<'de> for &'a mut KeyDeserializer<'b, 'de> {
+    type Error = DataError;
+    type Variant = Self;
+
+    #[inline]
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let index = self.take_u32()?;
+        let value = seed.deserialize(IntoDeserializer::<DataError>::into_deserializer(index))?;
+        Ok((value, self))
+    }
+}
+
+impl<'a, 'b, 'de> serde::de::VariantAccess<'de> for &'a mut KeyDeserializer<'b, 'de> {
+    type Error = DataError;
+
+    #[inline]
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    #[inline]
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    #[inline]
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        serde::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    #[inline]
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        serde::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
@@ -3,10 +3,14 @@ use crate::serde::error::DataError;
 use crate::{Data, DataReader};
 use serde::{Deserialize, Serialize};
 
+pub(crate) mod adapter;
+pub mod columnar;
 pub mod de;
 pub mod error;
+pub mod key;
 pub mod ser;
 pub mod serialize_impl;
+pub(crate) mod tag;
 
 impl Data {
     #[inline]
@@ -24,14 +28,39 @@ where
     pub fn serde_deserialize<T: Deserialize<'de>>(&'a mut self) -> Result<T, DataError> {
         T::deserialize(self)
     }
+
+    /// Deserialize into an existing value, reusing its allocations where the
+    /// type supports it (`Vec`, `String`, and structs derived with the
+    /// `in_place` feature).
+    #[inline]
+    pub fn deserialize_into<T: Deserialize<'de>>(
+        &'a mut self,
+        place: &mut T,
+    ) -> Result<(), DataError> {
+        T::deserialize_in_place(self, place)
+    }
 }
 
 impl DataOwnedReader {
     #[inline]
     pub fn serde_deserialize<'de, T: Deserialize<'de>>(&'de mut self) -> Result<T, DataError> {
         let mut dr = DataReader::from(&self.inner[self.offset..]);
+        dr.config = self.config;
         let v = dr.serde_deserialize()?;
         self.offset += dr.offset();
         Ok(v)
     }
+
+    /// Deserialize into an existing value, see [`DataReader::deserialize_into`].
+    #[inline]
+    pub fn deserialize_into<'de, T: Deserialize<'de>>(
+        &'de mut self,
+        place: &mut T,
+    ) -> Result<(), DataError> {
+        let mut dr = DataReader::from(&self.inner[self.offset..]);
+        dr.config = self.config;
+        dr.deserialize_into(place)?;
+        self.offset += dr.offset();
+        Ok(())
+    }
 }
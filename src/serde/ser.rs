@@ -1,10 +1,15 @@
+use crate::config::Profile;
+use crate::dotnet::NetNumber;
+use crate::java::JavaNumber;
+use crate::serde::adapter::Adapter;
 use crate::serde::error::DataError;
 use crate::serde::serialize_impl::{
     DataSerializeMap, DataSerializeSeq, DataSerializeTuple, DataSerializeTupleStruct,
     DataSerializeTupleVariant,
 };
-use crate::Data;
-use anyhow::Result;
+use crate::serde::tag;
+use crate::{Data, IntEncoding, WriteNumberFixed};
+use anyhow::{ensure, Result};
 use paste::paste;
 use serde::{Serialize, Serializer};
 
@@ -14,7 +19,27 @@ macro_rules! make_base_serialize {
         $(
         #[inline]
         fn [<serialize_ $type>] (self, v: $type) -> Result<Self::Ok, Self::Error> {
-            self.write_fixed(v);
+            self.write_type_tag(tag::[<$type:upper>]);
+            self.write_number(v);
+            Ok(())
+        }
+        )*
+      }
+    };
+}
+
+macro_rules! make_int_serialize {
+    ($($type:ty)+) => {
+      paste!{
+        $(
+        #[inline]
+        fn [<serialize_ $type>] (self, v: $type) -> Result<Self::Ok, Self::Error> {
+            self.write_type_tag(tag::[<$type:upper>]);
+            if self.varint() {
+                self.write_var_integer(v);
+            } else {
+                self.write_number(v);
+            }
             Ok(())
         }
         )*
@@ -22,6 +47,87 @@ macro_rules! make_base_serialize {
     };
 }
 
+impl Data {
+    /// write the type tag if the config is tagged
+    #[inline]
+    pub(crate) fn write_type_tag(&mut self, tag: u8) {
+        if self.config.tagged {
+            self.write_fixed(tag);
+        }
+    }
+
+    /// write a fixed size number, big endian for the Java profile
+    /// and little endian for the bincode and .NET ones
+    #[inline]
+    pub(crate) fn write_number<T: WriteNumberFixed + JavaNumber + NetNumber>(&mut self, v: T) {
+        match self.config.profile {
+            Profile::Java => self.write_java_fixed(v),
+            _ if self.config.little_endian() => self.write_net_fixed(v),
+            _ => self.write_fixed(v),
+        }
+    }
+
+    /// 16, 32 and 64 bit integers are written as varint
+    #[inline]
+    pub(crate) fn varint(&self) -> bool {
+        self.config.int_encoding == IntEncoding::Varint || self.adapter == Adapter::Varint
+    }
+
+    /// write a str with the length prefix of the config or the field adapter
+    #[inline]
+    pub(crate) fn write_str(&mut self, v: &str) -> Result<()> {
+        if self.config.profile == Profile::Java {
+            self.write_java_utf(v)
+        } else if self.adapter == Adapter::U16LenStr {
+            ensure!(
+                v.len() <= u16::MAX as usize,
+                "string length {} too large for u16 prefix",
+                v.len()
+            );
+            self.write_fixed(v.len() as u16);
+            self.write_buf(v.as_bytes());
+            Ok(())
+        } else {
+            self.try_write_fixed(v)
+        }
+    }
+
+    /// write the variant name, or its index with `variant_index`
+    #[inline]
+    pub(crate) fn write_variant(
+        &mut self,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        if self.config.foreign() {
+            self.write_number(variant_index);
+            Ok(())
+        } else if self.config.variant_index {
+            self.write_type_tag(tag::VARIANT);
+            self.write_var_integer(variant_index);
+            Ok(())
+        } else {
+            self.write_type_tag(tag::STR);
+            self.try_write_fixed(variant)
+        }
+    }
+
+    /// write the variant of a non-unit enum value,
+    /// tagged it becomes a single entry map from the variant to the content
+    #[inline]
+    pub(crate) fn write_variant_header(
+        &mut self,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        if self.config.tagged {
+            self.write_fixed(tag::MAP);
+            self.write_len(1)?;
+        }
+        self.write_variant(variant_index, variant)
+    }
+}
+
 impl<'a> Serializer for &'a mut Data {
     type Ok = ();
     type Error = DataError;
@@ -33,12 +139,36 @@ impl<'a> Serializer for &'a mut Data {
     type SerializeStruct = DataSerializeTupleStruct<'a>;
     type SerializeStructVariant = DataSerializeTupleVariant<'a>;
 
-    make_base_serialize!(bool i8 u8 i16 u16 i32 u32 i64 u64 f32 f64 i128 u128);
+    make_base_serialize!(i8 u8 f32 f64 i128 u128);
+    make_int_serialize!(i16 u16 i32 u32 i64 u64);
+
+    #[inline]
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        if self.config.tagged {
+            self.write_fixed(if v { tag::TRUE } else { tag::FALSE });
+        } else {
+            self.write_fixed(v);
+        }
+        Ok(())
+    }
 
     #[inline]
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        if self.config.profile == Profile::DotNet {
+            self.write_net_char(v)?;
+            return Ok(());
+        }
+        if self.config.profile == Profile::Java {
+            self.write_java_char(v)?;
+            return Ok(());
+        }
         let mut buf = [0; 4];
-        self.write_fixed(v.encode_utf8(&mut buf).as_bytes());
+        if self.config.foreign() {
+            self.write_buf(v.encode_utf8(&mut buf).as_bytes());
+            return Ok(());
+        }
+        self.write_type_tag(tag::CHAR);
+        self.write_str(v.encode_utf8(&mut buf))?;
         Ok(())
     }
 
@@ -47,7 +177,8 @@ impl<'a> Serializer for &'a mut Data {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        self.write_fixed(v);
+        self.write_type_tag(tag::STR);
+        self.write_str(v)?;
         Ok(())
     }
 
@@ -56,7 +187,8 @@ impl<'a> Serializer for &'a mut Data {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        self.write_fixed(v);
+        self.write_type_tag(tag::BYTES);
+        self.try_write_fixed(v)?;
         Ok(())
     }
 
@@ -65,19 +197,19 @@ impl<'a> Serializer for &'a mut Data {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        self.write_fixed(0u8);
+        self.write_fixed(tag::NONE);
         Ok(())
     }
 
     #[inline]
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        self.write_fixed(1u8);
+        self.write_fixed(tag::SOME);
         value.serialize(self)
     }
 
@@ -86,63 +218,71 @@ impl<'a> Serializer for &'a mut Data {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        self.write_fixed(1u8);
+        if self.config.tagged {
+            self.write_fixed(tag::UNIT);
+        } else if !self.config.foreign() {
+            self.write_fixed(1u8);
+        }
         Ok(())
     }
 
     #[inline]
     fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
-        if self.mode == 1 {
-            return Err(DataError::RollBack);
-        }
-        self.write_fixed(1u8);
-        Ok(())
+        self.serialize_unit()
     }
 
     #[inline]
     fn serialize_unit_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        self.write_fixed(variant);
+        self.write_variant(variant_index, variant)?;
         Ok(())
     }
 
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
+        if !self.config.tagged {
+            if let Some(adapter) = Adapter::from_name(name) {
+                let outer = std::mem::replace(&mut self.adapter, adapter);
+                let r = value.serialize(&mut *self);
+                self.adapter = outer;
+                return r;
+            }
+        }
         value.serialize(self)
     }
 
     #[inline]
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        self.write_fixed(variant);
+        self.write_variant_header(variant_index, variant)?;
         value.serialize(self)
     }
 
@@ -151,7 +291,7 @@ impl<'a> Serializer for &'a mut Data {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        Ok(DataSerializeSeq::new(self, len))
+        DataSerializeSeq::new(self, len)
     }
 
     #[inline]
@@ -159,7 +299,7 @@ impl<'a> Serializer for &'a mut Data {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        Ok(DataSerializeTuple::new(self, len))
+        DataSerializeTuple::new(self, len)
     }
 
     #[inline]
@@ -171,21 +311,21 @@ impl<'a> Serializer for &'a mut Data {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        Ok(DataSerializeTupleStruct::new(self, len))
+        DataSerializeTupleStruct::new(self, len)
     }
 
     #[inline]
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        Ok(DataSerializeTupleVariant::new(self, variant, len))
+        DataSerializeTupleVariant::new(self, variant_index, variant, len)
     }
 
     #[inline]
@@ -193,7 +333,7 @@ impl<'a> Serializer for &'a mut Data {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        Ok(DataSerializeMap::new(self, len))
+        DataSerializeMap::new(self, len)
     }
 
     #[inline]
@@ -205,20 +345,25 @@ impl<'a> Serializer for &'a mut Data {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        Ok(DataSerializeTupleStruct::new(self, len))
+        DataSerializeTupleStruct::new_struct(self, len)
     }
 
     #[inline]
     fn serialize_struct_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
         if self.mode == 1 {
             return Err(DataError::RollBack);
         }
-        Ok(DataSerializeTupleVariant::new(self, variant, len))
+        DataSerializeTupleVariant::new_struct(self, variant_index, variant, len)
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        self.config.human_readable
     }
 }
@@ -1,32 +1,27 @@
+use crate::serde::adapter::Adapter;
 use crate::serde::error::DataError;
+use crate::serde::tag;
 use crate::Data;
+use anyhow::anyhow;
 use serde::ser;
 use serde::Serialize;
-use std::io::Write;
 
 pub struct DataSerializeSeq<'a> {
-    write_mode: u8,
-    write_data: Option<Data>,
+    len_offset: Option<usize>,
+    count: usize,
     data: &'a mut Data,
 }
 
 impl<'a> DataSerializeSeq<'a> {
     #[inline]
-    pub fn new(data: &'a mut Data, len: Option<usize>) -> DataSerializeSeq<'a> {
-        if let Some(len) = len {
-            data.write_fixed(len as u32);
-            DataSerializeSeq {
-                write_mode: 0u8,
-                write_data: None,
-                data,
-            }
-        } else {
-            DataSerializeSeq {
-                write_mode: 1u8,
-                write_data: Some(Data::new()),
-                data,
-            }
-        }
+    pub fn new(data: &'a mut Data, len: Option<usize>) -> Result<DataSerializeSeq<'a>, DataError> {
+        data.write_type_tag(tag::SEQ);
+        let len_offset = reserve_len(data, len)?;
+        Ok(DataSerializeSeq {
+            len_offset,
+            count: 0,
+            data,
+        })
     }
 }
 
@@ -34,42 +29,57 @@ impl<'a> ser::SerializeSeq for DataSerializeSeq<'a> {
     type Ok = ();
     type Error = DataError;
     #[inline]
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        if self.write_mode == 0 {
-            value.serialize(&mut *self.data)
-        } else {
-            if let Some(ref mut table) = self.write_data {
-                value.serialize(table)?;
-            }
-            Ok(())
-        }
+        value.serialize(&mut *self.data)?;
+        self.count += 1;
+        Ok(())
     }
 
+    #[inline]
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        if self.write_mode == 0 {
-            Ok(())
-        } else {
-            if let Some(ref table) = self.write_data {
-                self.data.write_fixed(table.len() as u32);
-                self.data.write_all(table)?;
-            }
-            Ok(())
+        patch_len(self.data, self.len_offset, self.count)
+    }
+}
+
+/// write the length if it is known,
+/// otherwise reserve the slot and return its offset to be patched at `end()`
+#[inline]
+fn reserve_len(data: &mut Data, len: Option<usize>) -> Result<Option<usize>, DataError> {
+    match len {
+        Some(len) => {
+            data.write_len(len)?;
+            Ok(None)
         }
+        None => Ok(Some(data.reserve_len()?)),
     }
 }
 
+#[inline]
+fn patch_len(data: &mut Data, len_offset: Option<usize>, count: usize) -> Result<(), DataError> {
+    if let Some(offset) = len_offset {
+        data.patch_len(offset, count)?;
+    }
+    Ok(())
+}
+
 pub struct DataSerializeTuple<'a> {
     data: &'a mut Data,
 }
 
 impl<'a> DataSerializeTuple<'a> {
     #[inline]
-    pub fn new(data: &'a mut Data, len: usize) -> DataSerializeTuple<'_> {
-        data.write_fixed(len as u32);
-        DataSerializeTuple { data }
+    pub fn new(data: &'a mut Data, len: usize) -> Result<DataSerializeTuple<'a>, DataError> {
+        data.write_type_tag(tag::SEQ);
+        if data.adapter == Adapter::FixedArray {
+            // the array length is known by the reader, nested tuples keep theirs
+            data.adapter = Adapter::None;
+        } else if data.config.tuple_len() {
+            data.write_len(len)?;
+        }
+        Ok(DataSerializeTuple { data })
     }
 }
 
@@ -77,9 +87,9 @@ impl<'a> ser::SerializeTuple for DataSerializeTuple<'a> {
     type Ok = ();
     type Error = DataError;
     #[inline]
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(&mut *self.data)
     }
@@ -90,14 +100,97 @@ impl<'a> ser::SerializeTuple for DataSerializeTuple<'a> {
 }
 
 pub struct DataSerializeTupleStruct<'a> {
+    len_offset: Option<usize>,
+    count: usize,
     data: &'a mut Data,
 }
 
 impl<'a> DataSerializeTupleStruct<'a> {
     #[inline]
-    pub fn new(data: &'a mut Data, len: usize) -> DataSerializeTupleStruct<'a> {
-        data.write_fixed(len as u32);
-        DataSerializeTupleStruct { data }
+    pub fn new(data: &'a mut Data, _len: usize) -> Result<DataSerializeTupleStruct<'a>, DataError> {
+        data.write_type_tag(tag::SEQ);
+        let counted = data.config.tuple_len();
+        Self::begin(data, counted)
+    }
+
+    #[inline]
+    pub fn new_struct(
+        data: &'a mut Data,
+        _len: usize,
+    ) -> Result<DataSerializeTupleStruct<'a>, DataError> {
+        let counted = begin_struct(data);
+        Self::begin(data, counted)
+    }
+
+    #[inline]
+    fn begin(data: &'a mut Data, counted: bool) -> Result<DataSerializeTupleStruct<'a>, DataError> {
+        let len_offset = if counted {
+            Some(data.reserve_len()?)
+        } else {
+            None
+        };
+        Ok(DataSerializeTupleStruct {
+            len_offset,
+            count: 0,
+            data,
+        })
+    }
+}
+
+/// write the number of fields actually written in the count reserved up front,
+/// it may differ from the declared one when fields are skipped
+#[inline]
+fn patch_count(data: &mut Data, len_offset: Option<usize>, count: usize) -> Result<(), DataError> {
+    if let Some(offset) = len_offset {
+        data.patch_len(offset, count)?;
+    }
+    Ok(())
+}
+
+/// positional fields are matched by index, a skipped field would shift the rest
+#[inline]
+fn skip_struct_field(data: &mut Data, key: &'static str) -> Result<(), DataError> {
+    if data.config.positional_structs {
+        return Err(anyhow!("positional struct can't skip field:{}", key).into());
+    }
+    Ok(())
+}
+
+/// write the container tag of a struct,
+/// returns false for untagged positional structs, which have no field count
+#[inline]
+fn begin_struct(data: &mut Data) -> bool {
+    if data.config.positional_structs {
+        data.write_type_tag(tag::SEQ);
+        data.config.tagged
+    } else {
+        data.write_type_tag(tag::MAP);
+        true
+    }
+}
+
+/// write the field name and value, positional structs write only the value,
+/// with `skippable_fields` the value is prefixed by its byte length,
+/// tagged values are skippable already and are not prefixed
+#[inline]
+fn serialize_struct_field<T>(data: &mut Data, key: &'static str, value: &T) -> Result<(), DataError>
+where
+    T: ?Sized + Serialize,
+{
+    if data.config.positional_structs {
+        return value.serialize(data);
+    }
+    data.write_type_tag(tag::STR);
+    data.try_write_fixed(key)?;
+    if data.config.skippable_fields && !data.config.tagged {
+        let len_offset = data.reserve_len()?;
+        let start = data.begin_len();
+        value.serialize(&mut *data)?;
+        let len = data.end_len(start)?;
+        data.patch_len(len_offset, len)?;
+        Ok(())
+    } else {
+        value.serialize(data)
     }
 }
 
@@ -105,15 +198,17 @@ impl<'a> ser::SerializeTupleStruct for DataSerializeTupleStruct<'a> {
     type Ok = ();
     type Error = DataError;
     #[inline]
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.data)
+        value.serialize(&mut *self.data)?;
+        self.count += 1;
+        Ok(())
     }
     #[inline]
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        patch_count(self.data, self.len_offset, self.count)
     }
 }
 
@@ -121,24 +216,27 @@ impl<'a> ser::SerializeStruct for DataSerializeTupleStruct<'a> {
     type Ok = ();
     type Error = DataError;
     #[inline]
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        self.data.write_fixed(key);
-        value.serialize(&mut *self.data)
+        serialize_struct_field(self.data, key, value)?;
+        self.count += 1;
+        Ok(())
+    }
+    #[inline]
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+        skip_struct_field(self.data, key)
     }
     #[inline]
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        patch_count(self.data, self.len_offset, self.count)
     }
 }
 
 pub struct DataSerializeTupleVariant<'a> {
+    len_offset: Option<usize>,
+    count: usize,
     data: &'a mut Data,
 }
 
@@ -146,12 +244,43 @@ impl<'a> DataSerializeTupleVariant<'a> {
     #[inline]
     pub fn new(
         data: &'a mut Data,
+        variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<DataSerializeTupleVariant<'a>, DataError> {
+        data.write_variant_header(variant_index, variant)?;
+        data.write_type_tag(tag::SEQ);
+        let counted = data.config.tuple_len();
+        Self::begin(data, counted)
+    }
+
+    #[inline]
+    pub fn new_struct(
+        data: &'a mut Data,
+        variant_index: u32,
         variant: &'static str,
-        len: usize,
-    ) -> DataSerializeTupleVariant<'a> {
-        data.write_fixed(variant);
-        data.write_fixed(len as u32);
-        DataSerializeTupleVariant { data }
+        _len: usize,
+    ) -> Result<DataSerializeTupleVariant<'a>, DataError> {
+        data.write_variant_header(variant_index, variant)?;
+        let counted = begin_struct(data);
+        Self::begin(data, counted)
+    }
+
+    #[inline]
+    fn begin(
+        data: &'a mut Data,
+        counted: bool,
+    ) -> Result<DataSerializeTupleVariant<'a>, DataError> {
+        let len_offset = if counted {
+            Some(data.reserve_len()?)
+        } else {
+            None
+        };
+        Ok(DataSerializeTupleVariant {
+            len_offset,
+            count: 0,
+            data,
+        })
     }
 }
 
@@ -159,15 +288,17 @@ impl<'a> ser::SerializeTupleVariant for DataSerializeTupleVariant<'a> {
     type Ok = ();
     type Error = DataError;
     #[inline]
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.data)
+        value.serialize(&mut *self.data)?;
+        self.count += 1;
+        Ok(())
     }
     #[inline]
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        patch_count(self.data, self.len_offset, self.count)
     }
 }
 
@@ -175,49 +306,40 @@ impl<'a> ser::SerializeStructVariant for DataSerializeTupleVariant<'a> {
     type Ok = ();
     type Error = DataError;
     #[inline]
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        self.data.write_fixed(key);
-        value.serialize(&mut *self.data)
+        serialize_struct_field(self.data, key, value)?;
+        self.count += 1;
+        Ok(())
+    }
+    #[inline]
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+        skip_struct_field(self.data, key)
     }
     #[inline]
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        patch_count(self.data, self.len_offset, self.count)
     }
 }
 
 pub struct DataSerializeMap<'a> {
-    write_mode: u8,
-    write_key: Option<Vec<Data>>,
-    write_value: Option<Vec<Data>>,
+    len_offset: Option<usize>,
+    count: usize,
     data: &'a mut Data,
 }
 
 impl<'a> DataSerializeMap<'a> {
     #[inline]
-    pub fn new(data: &'a mut Data, len: Option<usize>) -> DataSerializeMap<'a> {
-        if let Some(len) = len {
-            data.write_fixed(len as u32);
-            DataSerializeMap {
-                write_mode: 0u8,
-                write_key: None,
-                write_value: None,
-                data,
-            }
-        } else {
-            DataSerializeMap {
-                write_mode: 1u8,
-                write_key: Some(Vec::new()),
-                write_value: Some(Vec::new()),
-                data,
-            }
-        }
+    pub fn new(data: &'a mut Data, len: Option<usize>) -> Result<DataSerializeMap<'a>, DataError> {
+        data.write_type_tag(tag::MAP);
+        let len_offset = reserve_len(data, len)?;
+        Ok(DataSerializeMap {
+            len_offset,
+            count: 0,
+            data,
+        })
     }
 }
 
@@ -225,55 +347,23 @@ impl<'a> ser::SerializeMap for DataSerializeMap<'a> {
     type Ok = ();
     type Error = DataError;
     #[inline]
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<(), Self::Error>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        if self.write_mode == 0 {
-            key.serialize(&mut *self.data)
-        } else {
-            if let Some(ref mut table) = self.write_key {
-                let mut data = Data::new();
-                key.serialize(&mut data)?;
-                table.push(data);
-            }
-            Ok(())
-        }
+        key.serialize(&mut *self.data)?;
+        self.count += 1;
+        Ok(())
     }
     #[inline]
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        if self.write_mode == 0 {
-            value.serialize(&mut *self.data)
-        } else {
-            if let Some(ref mut table) = self.write_value {
-                let mut data = Data::new();
-                value.serialize(&mut data)?;
-                table.push(data);
-            }
-            Ok(())
-        }
+        value.serialize(&mut *self.data)
     }
     #[inline]
-    fn end(mut self) -> Result<Self::Ok, Self::Error> {
-        if self.write_mode == 0 {
-            Ok(())
-        } else {
-            if let Some(ref mut keys) = self.write_key {
-                if let Some(ref mut values) = self.write_value {
-                    assert_eq!(keys.len(), values.len());
-                    self.data.write_fixed(keys.len() as u32);
-                    for i in 0..keys.len() {
-                        let key = keys.get(i).unwrap();
-                        let value = values.get(i).unwrap();
-                        self.data.write_all(key)?;
-                        self.data.write_all(value)?;
-                    }
-                }
-            }
-            Ok(())
-        }
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        patch_len(self.data, self.len_offset, self.count)
     }
 }
@@ -0,0 +1,25 @@
+//! Type tags written before every value when `Config::tagged` is enabled.
+
+pub const NONE: u8 = 0;
+pub const SOME: u8 = 1;
+pub const UNIT: u8 = 2;
+pub const FALSE: u8 = 3;
+pub const TRUE: u8 = 4;
+pub const I8: u8 = 5;
+pub const I16: u8 = 6;
+pub const I32: u8 = 7;
+pub const I64: u8 = 8;
+pub const I128: u8 = 9;
+pub const U8: u8 = 10;
+pub const U16: u8 = 11;
+pub const U32: u8 = 12;
+pub const U64: u8 = 13;
+pub const U128: u8 = 14;
+pub const F32: u8 = 15;
+pub const F64: u8 = 16;
+pub const CHAR: u8 = 17;
+pub const STR: u8 = 18;
+pub const BYTES: u8 = 19;
+pub const SEQ: u8 = 20;
+pub const MAP: u8 = 21;
+pub const VARIANT: u8 = 22;
@@ -0,0 +1,233 @@
+//! Type-Length-Value records with a configurable tag and length width.
+//!
+//! A value is either raw bytes or a container of nested records, whose length
+//! is patched once its content is written. Readers get each value as its own
+//! `DataReader`, so records with unknown tags are skipped by ignoring them.
+
+use crate::{Data, DataReader};
+use anyhow::{ensure, Result};
+
+/// The encoding of a tag or a length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlvWidth {
+    U8,
+    U16,
+    U32,
+    /// unsigned LEB128 varint
+    Varint,
+}
+
+impl TlvWidth {
+    #[inline]
+    fn max(self) -> u64 {
+        match self {
+            TlvWidth::U8 => u8::MAX as u64,
+            TlvWidth::U16 => u16::MAX as u64,
+            TlvWidth::U32 | TlvWidth::Varint => u32::MAX as u64,
+        }
+    }
+}
+
+/// The layout of a TLV record, the writer and the reader must use the same one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlvFormat {
+    pub(crate) tag: TlvWidth,
+    pub(crate) len: TlvWidth,
+    pub(crate) big_endian: bool,
+}
+
+impl TlvFormat {
+    /// fixed width tags and lengths are little endian
+    #[inline]
+    pub const fn new(tag: TlvWidth, len: TlvWidth) -> Self {
+        TlvFormat {
+            tag,
+            len,
+            big_endian: false,
+        }
+    }
+
+    /// Write fixed width tags and lengths in big endian.
+    #[inline]
+    pub const fn big_endian(mut self, enable: bool) -> Self {
+        self.big_endian = enable;
+        self
+    }
+
+    #[inline]
+    fn write(&self, data: &mut Data, width: TlvWidth, v: u64) -> Result<()> {
+        ensure!(
+            v <= width.max(),
+            "tlv value {} too large for {:?}",
+            v,
+            width
+        );
+        let bytes = if self.big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        };
+        let size = match width {
+            TlvWidth::U8 => 1,
+            TlvWidth::U16 => 2,
+            TlvWidth::U32 => 4,
+            TlvWidth::Varint => {
+                data.write_var_integer(v);
+                return Ok(());
+            }
+        };
+        if self.big_endian {
+            data.write_buf(&bytes[8 - size..]);
+        } else {
+            data.write_buf(&bytes[..size]);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn read(&self, dr: &mut DataReader, width: TlvWidth) -> Result<u64> {
+        let size = match width {
+            TlvWidth::U8 => 1,
+            TlvWidth::U16 => 2,
+            TlvWidth::U32 => 4,
+            TlvWidth::Varint => {
+                let v = dr.read_var_integer::<u64>()?;
+                ensure!(
+                    v <= width.max(),
+                    "tlv value {} too large for {:?}",
+                    v,
+                    width
+                );
+                return Ok(v);
+            }
+        };
+        let mut bytes = [0; 8];
+        if self.big_endian {
+            dr.read_buff(&mut bytes[8 - size..])?;
+            Ok(u64::from_be_bytes(bytes))
+        } else {
+            dr.read_buff(&mut bytes[..size])?;
+            Ok(u64::from_le_bytes(bytes))
+        }
+    }
+}
+
+impl Data {
+    /// write a record holding `value`, nothing is written on error
+    #[inline]
+    pub fn write_tlv(&mut self, format: TlvFormat, tag: u32, value: &[u8]) -> Result<()> {
+        let start = self.len();
+        let res = write_tlv(self, format, tag, value);
+        if res.is_err() {
+            self.truncate(start);
+        }
+        res
+    }
+
+    /// write a record whose value is written by the closure,
+    /// usually nested records, the length is patched afterwards,
+    /// nothing is written on error
+    #[inline]
+    pub fn write_tlv_container<F>(&mut self, format: TlvFormat, tag: u32, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Data) -> Result<()>,
+    {
+        let start = self.len();
+        let res = write_tlv_container(self, format, tag, f);
+        if res.is_err() {
+            self.truncate(start);
+        }
+        res
+    }
+}
+
+#[inline]
+fn write_tlv(data: &mut Data, format: TlvFormat, tag: u32, value: &[u8]) -> Result<()> {
+    format.write(data, format.tag, tag as u64)?;
+    format.write(data, format.len, value.len() as u64)?;
+    data.write_buf(value);
+    Ok(())
+}
+
+#[inline]
+fn write_tlv_container<F>(data: &mut Data, format: TlvFormat, tag: u32, f: F) -> Result<()>
+where
+    F: FnOnce(&mut Data) -> Result<()>,
+{
+    format.write(data, format.tag, tag as u64)?;
+    let len_offset = if format.len == TlvWidth::Varint {
+        data.reserve_var_len()
+    } else {
+        let len_offset = data.len();
+        format.write(data, format.len, 0)?;
+        len_offset
+    };
+    let start = data.begin_len();
+    f(data)?;
+    let len = data.end_len(start)?;
+    if format.len == TlvWidth::Varint {
+        ensure!(
+            len as u64 <= format.len.max(),
+            "tlv value {} too large for {:?}",
+            len,
+            format.len
+        );
+        return data.patch_var_len(len_offset, len);
+    }
+    let mut prefix = Data::with_capacity(4);
+    format.write(&mut prefix, format.len, len as u64)?;
+    data.write_buf_at(len_offset, &prefix)?;
+    data.compact_lens();
+    Ok(())
+}
+
+impl<'a> DataReader<'a> {
+    /// read a record, returns its tag and a reader over its value
+    #[inline]
+    pub fn read_tlv(&mut self, format: TlvFormat) -> Result<(u32, DataReader<'a>)> {
+        let tag = format.read(self, format.tag)? as u32;
+        let len = format.read(self, format.len)? as usize;
+        ensure!(
+            len <= self.len(),
+            "tlv value size too big,{}>{}",
+            len,
+            self.len()
+        );
+        let (value, have) = self.buff.split_at(len);
+        self.buff = have;
+        let mut value = DataReader::from(value);
+        value.config = self.config;
+        Ok((tag, value))
+    }
+
+    /// iterate the records up to the end of the reader
+    #[inline]
+    pub fn tlv_iter<'r>(&'r mut self, format: TlvFormat) -> TlvIter<'r, 'a> {
+        TlvIter {
+            reader: self,
+            format,
+            failed: false,
+        }
+    }
+}
+
+/// Yields the `(tag, value)` of each record, stops after the first error.
+pub struct TlvIter<'r, 'a> {
+    reader: &'r mut DataReader<'a>,
+    format: TlvFormat,
+    failed: bool,
+}
+
+impl<'r, 'a> Iterator for TlvIter<'r, 'a> {
+    type Item = Result<(u32, DataReader<'a>)>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.reader.is_empty() {
+            return None;
+        }
+        let record = self.reader.read_tlv(self.format);
+        self.failed = record.is_err();
+        Some(record)
+    }
+}
@@ -0,0 +1,272 @@
+//! Varint encodings used by other formats, next to the crate's own
+//! LEB128 with zigzag of `write_var_integer`.
+//!
+//! Values are u64, `Sleb128` takes the bits of an i64, see `write_sleb128`.
+//! Decoding fails on truncated input and on values that don't fit 64 bits.
+
+use crate::{Data, DataReader};
+use anyhow::{bail, ensure, Result};
+
+/// A varint encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarintFlavor {
+    /// unsigned LEB128, 7 bits per byte low first, the high bit marks a next byte
+    Leb128,
+    /// signed LEB128 as in DWARF and WASM, the last byte's bit 6 is the sign
+    Sleb128,
+    /// big endian groups of 7 bits as in MIDI, the high bit marks a next byte
+    Vlq,
+    /// VLQ of git packfile delta offsets, each next byte also adds one
+    GitOffset,
+    /// the leading ones of the first byte count the big endian bytes after it,
+    /// its other bits are the high bits of the value. Keys sort like the values.
+    Prefix,
+    /// MySQL length-encoded integer: one byte below 251,
+    /// else `0xfc`, `0xfd` or `0xfe` and 2, 3 or 8 little endian bytes
+    MySql,
+}
+
+impl VarintFlavor {
+    /// byte size of `v` encoded in this flavor
+    #[inline]
+    pub const fn size(self, v: u64) -> usize {
+        let bits = 64 - v.leading_zeros() as usize;
+        match self {
+            VarintFlavor::Leb128 | VarintFlavor::Vlq => {
+                if bits == 0 {
+                    1
+                } else {
+                    bits.div_ceil(7)
+                }
+            }
+            VarintFlavor::Sleb128 => {
+                let v = v as i64;
+                let v = if v < 0 { !v } else { v };
+                // the sign bit needs room too
+                (65 - v.leading_zeros() as usize).div_ceil(7)
+            }
+            VarintFlavor::GitOffset => {
+                let mut size = 1;
+                let mut v = v >> 7;
+                while v != 0 {
+                    v = (v - 1) >> 7;
+                    size += 1;
+                }
+                size
+            }
+            VarintFlavor::Prefix => {
+                let extra = bits.saturating_sub(7).div_ceil(7);
+                if extra > 8 {
+                    9
+                } else {
+                    extra + 1
+                }
+            }
+            VarintFlavor::MySql => {
+                if v < 251 {
+                    1
+                } else if v <= 0xffff {
+                    3
+                } else if v <= 0xff_ffff {
+                    4
+                } else {
+                    9
+                }
+            }
+        }
+    }
+
+    /// encode `v`, returns the buffer and the used size
+    #[inline]
+    fn encode(self, v: u64) -> ([u8; 10], usize) {
+        let mut buff = [0; 10];
+        let size = self.size(v);
+        match self {
+            VarintFlavor::Leb128 => {
+                let mut v = v;
+                for b in buff[..size].iter_mut() {
+                    *b = (v as u8 & 0x7f) | 0x80;
+                    v >>= 7;
+                }
+                buff[size - 1] &= 0x7f;
+            }
+            VarintFlavor::Sleb128 => {
+                let mut v = v as i64;
+                for b in buff[..size].iter_mut() {
+                    *b = (v as u8 & 0x7f) | 0x80;
+                    v >>= 7;
+                }
+                buff[size - 1] &= 0x7f;
+            }
+            VarintFlavor::Vlq => {
+                let mut v = v;
+                for b in buff[..size].iter_mut().rev() {
+                    *b = (v as u8 & 0x7f) | 0x80;
+                    v >>= 7;
+                }
+                buff[size - 1] &= 0x7f;
+            }
+            VarintFlavor::GitOffset => {
+                let mut v = v;
+                for b in buff[..size].iter_mut().rev() {
+                    *b = (v as u8 & 0x7f) | 0x80;
+                    v = (v >> 7).wrapping_sub(1);
+                }
+                buff[size - 1] &= 0x7f;
+            }
+            VarintFlavor::Prefix => {
+                let extra = size - 1;
+                if extra == 8 {
+                    buff[0] = 0xff;
+                } else {
+                    buff[0] = !(0xffu8 >> extra) | (v >> (8 * extra)) as u8;
+                }
+                buff[1..size].copy_from_slice(&v.to_be_bytes()[8 - extra..]);
+            }
+            VarintFlavor::MySql => {
+                let extra = match size {
+                    1 => {
+                        buff[0] = v as u8;
+                        0
+                    }
+                    3 => {
+                        buff[0] = 0xfc;
+                        2
+                    }
+                    4 => {
+                        buff[0] = 0xfd;
+                        3
+                    }
+                    _ => {
+                        buff[0] = 0xfe;
+                        8
+                    }
+                };
+                buff[1..size].copy_from_slice(&v.to_le_bytes()[..extra]);
+            }
+        }
+        (buff, size)
+    }
+
+    /// decode a value from the start of `buff`, returns it and the used size
+    #[inline]
+    fn decode(self, buff: &[u8]) -> Result<(u64, usize)> {
+        let byte = |i: usize| match buff.get(i) {
+            Some(&b) => Ok(b),
+            None => bail!("read {:?} varint,offset:{} > bytes length", self, i),
+        };
+        match self {
+            VarintFlavor::Leb128 | VarintFlavor::Sleb128 => {
+                let mut v = 0u64;
+                for i in 0..10 {
+                    let b = byte(i)?;
+                    if i == 9 {
+                        // only the 64th bit is left, the rest must be zero or its sign extension
+                        let valid = match self {
+                            VarintFlavor::Leb128 => b <= 1,
+                            _ => b == 0 || b == 0x7f,
+                        };
+                        ensure!(valid, "{:?} varint overflows 64 bits", self);
+                        return Ok((v | (b as u64) << 63, 10));
+                    }
+                    v |= ((b & 0x7f) as u64) << (7 * i);
+                    if b & 0x80 == 0 {
+                        let bits = 7 * (i + 1);
+                        if self == VarintFlavor::Sleb128 && b & 0x40 != 0 {
+                            v |= !0 << bits;
+                        }
+                        return Ok((v, i + 1));
+                    }
+                }
+                unreachable!()
+            }
+            VarintFlavor::Vlq | VarintFlavor::GitOffset => {
+                let mut v = 0u64;
+                let mut i = 0;
+                loop {
+                    let b = byte(i)?;
+                    if i > 0 && self == VarintFlavor::GitOffset {
+                        v = match v.checked_add(1) {
+                            Some(v) => v,
+                            None => bail!("{:?} varint overflows 64 bits", self),
+                        };
+                    }
+                    ensure!(v >> 57 == 0, "{:?} varint overflows 64 bits", self);
+                    v = (v << 7) | (b & 0x7f) as u64;
+                    i += 1;
+                    if b & 0x80 == 0 {
+                        return Ok((v, i));
+                    }
+                }
+            }
+            VarintFlavor::Prefix => {
+                let first = byte(0)?;
+                let extra = first.leading_ones() as usize;
+                ensure!(
+                    extra < buff.len(),
+                    "read {:?} varint,offset:{} > bytes length",
+                    self,
+                    extra
+                );
+                let mut v = if extra == 8 {
+                    0
+                } else {
+                    (first & (0x7f >> extra)) as u64
+                };
+                for &b in &buff[1..=extra] {
+                    v = (v << 8) | b as u64;
+                }
+                Ok((v, extra + 1))
+            }
+            VarintFlavor::MySql => {
+                let extra = match byte(0)? {
+                    b @ 0..=250 => return Ok((b as u64, 1)),
+                    0xfc => 2,
+                    0xfd => 3,
+                    0xfe => 8,
+                    b => bail!("{:#x} is not a MySQL length-encoded integer", b),
+                };
+                ensure!(
+                    extra < buff.len(),
+                    "read {:?} varint,offset:{} > bytes length",
+                    self,
+                    extra
+                );
+                let mut le = [0; 8];
+                le[..extra].copy_from_slice(&buff[1..=extra]);
+                Ok((u64::from_le_bytes(le), extra + 1))
+            }
+        }
+    }
+}
+
+impl Data {
+    /// write `v` in `flavor`
+    #[inline]
+    pub fn write_varint_as(&mut self, flavor: VarintFlavor, v: u64) {
+        let (buff, size) = flavor.encode(v);
+        self.write_buf(&buff[..size]);
+    }
+
+    /// write `v` as signed LEB128
+    #[inline]
+    pub fn write_sleb128(&mut self, v: i64) {
+        self.write_varint_as(VarintFlavor::Sleb128, v as u64)
+    }
+}
+
+impl<'a> DataReader<'a> {
+    /// read a value in `flavor`, nothing is consumed on error
+    #[inline]
+    pub fn read_varint_as(&mut self, flavor: VarintFlavor) -> Result<u64> {
+        let (v, size) = flavor.decode(self.buff)?;
+        self.advance(size)?;
+        Ok(v)
+    }
+
+    /// read a signed LEB128
+    #[inline]
+    pub fn read_sleb128(&mut self) -> Result<i64> {
+        Ok(self.read_varint_as(VarintFlavor::Sleb128)? as i64)
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use data_rw::bits::{BitReader, BitWriter};
+use data_rw::{Data, DataReader};
+
+#[test]
+fn test_bits() -> Result<()> {
+    let mut data = Data::new();
+    let mut bits = BitWriter::new(&mut data);
+    bits.write_bool(true)?;
+    bits.write_bits(5, 3)?;
+    bits.write_signed(-3, 5)?;
+    bits.write_bits(0x1ff, 9)?;
+    bits.write_bits(u64::MAX, 64)?;
+    bits.write_signed(i64::MIN, 64)?;
+    bits.write_quantized(0.5, -1.0, 1.0, 10)?;
+    bits.write_quantized(7.0, -1.0, 1.0, 4)?;
+    bits.write_bitmap(&[true, false, true])?;
+    assert!(!bits.is_aligned());
+    // byte level writes after align
+    bits.data().write_fixed(0xabcdu16);
+    bits.write_bits(1, 2)?;
+
+    assert_eq!(&data[..2], &[0b1101_1011, 0b1111_1111]);
+    assert_eq!(data.len(), 1 + 2 + 16 + 2 + 2 + 1);
+
+    let mut rd = DataReader::from(&data);
+    let mut bits = BitReader::new(&mut rd);
+    assert!(bits.read_bool()?);
+    assert_eq!(bits.read_bits(3)?, 5);
+    assert_eq!(bits.read_signed(5)?, -3);
+    assert_eq!(bits.read_bits(9)?, 0x1ff);
+    assert_eq!(bits.read_bits(64)?, u64::MAX);
+    assert_eq!(bits.read_signed(64)?, i64::MIN);
+    let v = bits.read_quantized(-1.0, 1.0, 10)?;
+    assert!((v - 0.5).abs() < 2.0 / 1023.0);
+    assert_eq!(bits.read_quantized(-1.0, 1.0, 4)?, 1.0);
+    assert_eq!(bits.read_bitmap(3)?, vec![true, false, true]);
+    assert_eq!(bits.reader().read_fixed::<u16>()?, 0xabcd);
+    assert_eq!(bits.read_bits(2)?, 1);
+    bits.align();
+    assert!(rd.is_empty());
+
+    let mut data = Data::new();
+    let mut bits = BitWriter::new(&mut data);
+    assert!(bits.write_bits(8, 3).is_err());
+    assert!(bits.write_signed(4, 3).is_err());
+    assert!(bits.write_signed(-5, 3).is_err());
+    assert!(bits.write_bits(0, 65).is_err());
+    assert!(bits.write_quantized(f64::NAN, 0.0, 1.0, 8).is_err());
+    assert!(bits.write_quantized(0.0, 1.0, 1.0, 8).is_err());
+    assert!(bits.write_quantized(0.0, 0.0, 1.0, 33).is_err());
+    assert!(data.is_empty());
+
+    let mut rd = DataReader::from(&[0xff]);
+    let mut bits = BitReader::new(&mut rd);
+    assert_eq!(bits.read_bits(7)?, 0x7f);
+    assert!(bits.read_bits(2).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_bitmap() -> Result<()> {
+    let flags = (0..21).map(|i| i % 3 == 0).collect::<Vec<_>>();
+    let mut data = Data::new();
+    data.write_bitmap(&flags);
+    data.write_bitmap(&[]);
+    assert_eq!(&data[..], &[21, 0b0100_1001, 0b1001_0010, 0b0000_0100, 0]);
+
+    let mut rd = DataReader::from(&data);
+    assert_eq!(rd.read_bitmap()?, flags);
+    assert_eq!(rd.read_bitmap()?, vec![]);
+    assert!(rd.is_empty());
+    assert!(DataReader::from(&[9, 0]).read_bitmap().is_err());
+    Ok(())
+}
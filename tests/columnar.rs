@@ -0,0 +1,176 @@
+use anyhow::Result;
+use data_rw::{Config, Data, DataReader};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+enum Kind {
+    Buy,
+    Sell { limit: f64 },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Point {
+    x: i16,
+    y: i16,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Trade {
+    id: u64,
+    price: f64,
+    active: bool,
+    symbol: String,
+    note: Option<String>,
+    kind: Kind,
+    at: Point,
+    tags: Vec<u8>,
+}
+
+fn trades() -> Vec<Trade> {
+    (0..100)
+        .map(|i| Trade {
+            id: 1000 + i,
+            price: i as f64 * 0.5,
+            active: i % 3 == 0,
+            symbol: format!("SYM{}", i % 7),
+            note: if i % 2 == 0 {
+                Some(i.to_string())
+            } else {
+                None
+            },
+            kind: if i % 4 == 0 {
+                Kind::Buy
+            } else {
+                Kind::Sell { limit: i as f64 }
+            },
+            at: Point {
+                x: i as i16,
+                y: -(i as i16),
+            },
+            tags: vec![i as u8; (i % 3) as usize],
+        })
+        .collect()
+}
+
+#[test]
+fn test_columnar_round_trip() -> Result<()> {
+    let rows = trades();
+    for config in [Config::new(), Config::bincode()] {
+        let mut data = Data::new();
+        data.set_config(config);
+        data.write_columnar(&rows)?;
+        data.write_fixed(0x55u8);
+
+        let mut rd = DataReader::from(&data);
+        rd.set_config(config);
+        assert_eq!(rd.read_columnar::<Trade>()?, rows);
+        assert_eq!(rd.read_fixed::<u8>()?, 0x55);
+        assert!(rd.is_empty());
+    }
+
+    let mut data = Data::new();
+    data.write_columnar::<Trade>(&[])?;
+    assert_eq!(DataReader::from(&data).read_columnar::<Trade>()?, vec![]);
+    Ok(())
+}
+
+#[test]
+fn test_columnar_read_column() -> Result<()> {
+    let rows = trades();
+    let mut data = Data::new();
+    data.write_columnar(&rows)?;
+
+    let prices = DataReader::from(&data).read_column::<f64>("price")?;
+    assert_eq!(prices, rows.iter().map(|r| r.price).collect::<Vec<_>>());
+
+    let symbols = DataReader::from(&data).read_column::<&str>("symbol")?;
+    assert_eq!(symbols, rows.iter().map(|r| &r.symbol).collect::<Vec<_>>());
+
+    let kinds = DataReader::from(&data).read_column::<Kind>("kind")?;
+    assert_eq!(
+        kinds,
+        rows.iter().map(|r| r.kind.clone()).collect::<Vec<_>>()
+    );
+
+    assert!(DataReader::from(&data)
+        .read_column::<u8>("missing")
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_columnar_projection() -> Result<()> {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Price {
+        id: u64,
+        price: f64,
+    }
+
+    let rows = trades();
+    let mut data = Data::new();
+    data.write_columnar(&rows)?;
+    let prices = DataReader::from(&data).read_columnar::<Price>()?;
+    assert_eq!(prices.len(), rows.len());
+    assert_eq!(
+        prices[3],
+        Price {
+            id: 1003,
+            price: 1.5
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_columnar_with() -> Result<()> {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Batch {
+        name: String,
+        #[serde(with = "data_rw::columnar")]
+        points: Vec<Point>,
+    }
+
+    let batch = Batch {
+        name: "batch".to_string(),
+        points: (0..10).map(|i| Point { x: i, y: i * 2 }).collect(),
+    };
+    let mut data = Data::new();
+    data.serde_serialize(&batch)?;
+    let mut rd = DataReader::from(&data);
+    assert_eq!(rd.serde_deserialize::<Batch>()?, batch);
+    assert!(rd.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_columnar_errors() -> Result<()> {
+    #[derive(Serialize)]
+    struct Skip {
+        a: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        b: Option<u32>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Mixed {
+        Int { v: u32 },
+        Str { v: String },
+    }
+
+    let mut data = Data::new();
+    assert!(data.write_columnar(&[1u32, 2]).is_err());
+    assert!(data
+        .write_columnar(&[Skip { a: 1, b: Some(1) }, Skip { a: 2, b: None }])
+        .is_err());
+    assert!(data
+        .write_columnar(&[Mixed::Int { v: 1 }, Mixed::Str { v: "x".to_string() }])
+        .is_err());
+    assert!(data.is_empty());
+
+    data.write_columnar(&trades())?;
+    let len = data.len();
+    data.truncate(len - 1);
+    assert!(DataReader::from(&data).read_columnar::<Trade>().is_err());
+    Ok(())
+}
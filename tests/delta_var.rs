@@ -0,0 +1,125 @@
+use anyhow::Result;
+use data_rw::{Data, DataReader};
+use serde::{Deserialize, Serialize};
+
+fn sorted() -> Vec<u64> {
+    (0..1000u64)
+        .map(|i| 1_000_000 + i * i % 7 + i * 13)
+        .collect()
+}
+
+#[test]
+fn test_delta_var_slice() -> Result<()> {
+    let cases = [
+        vec![],
+        vec![u64::MAX],
+        sorted(),
+        vec![5, 3, 3, 0, u64::MAX, 0, 1 << 63, 7],
+    ];
+    for values in cases.iter() {
+        let mut data = Data::new();
+        data.write_delta_var_slice(values);
+        data.write_fixed(0x55u8);
+        let mut rd = DataReader::from(&data);
+        assert_eq!(&rd.read_delta_var_slice()?, values);
+        assert_eq!(rd.read_fixed::<u8>()?, 0x55);
+        assert!(rd.is_empty());
+
+        for restart in [1, 3, 16, 5000] {
+            let mut data = Data::new();
+            data.write_delta_var_blocks(values, restart)?;
+            data.write_fixed(0x55u8);
+            let mut rd = DataReader::from(&data);
+            assert_eq!(&rd.read_delta_var_slice()?, values);
+            assert_eq!(rd.read_fixed::<u8>()?, 0x55);
+            assert!(rd.is_empty());
+        }
+    }
+
+    // sorted lists cost about a byte a value instead of a full varint
+    let values = sorted();
+    let mut data = Data::new();
+    data.write_delta_var_slice(&values);
+    assert!(data.len() < values.len() + 8);
+    Ok(())
+}
+
+#[test]
+fn test_delta_var_blocks() -> Result<()> {
+    let values = sorted();
+    let mut data = Data::new();
+    data.write_delta_var_blocks(&values, 16)?;
+    data.write_fixed(0x55u8);
+
+    let mut rd = DataReader::from(&data);
+    let blocks = rd.read_delta_var_blocks()?;
+    assert_eq!(rd.read_fixed::<u8>()?, 0x55);
+    assert_eq!(blocks.len(), values.len() as u64);
+
+    for i in [0, 1, 15, 16, 17, 500, 999] {
+        assert_eq!(blocks.get(i)?, values[i as usize]);
+        let rest = blocks.iter_from(i)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rest, &values[i as usize..]);
+    }
+    assert!(blocks.get(1000).is_err());
+    assert_eq!(blocks.iter_from(1000)?.count(), 0);
+    assert_eq!(blocks.iter().collect::<Result<Vec<_>>>()?, values);
+
+    for target in [0, 1_000_000, 1_000_001, 1_006_000, values[640], u64::MAX] {
+        let expect = values.partition_point(|&v| v < target) as u64;
+        assert_eq!(blocks.lower_bound(target)?, expect, "{}", target);
+    }
+
+    let mut data = Data::new();
+    data.write_delta_var_blocks(&[3, 1, 2], 2)?;
+    let blocks = DataReader::from(&data).read_delta_var_blocks()?;
+    assert_eq!(blocks.get(2)?, 2);
+    assert!(blocks.lower_bound(2).is_err());
+
+    let mut data = Data::new();
+    data.write_delta_var_slice(&values);
+    assert!(DataReader::from(&data).read_delta_var_blocks().is_err());
+    assert!(data.write_delta_var_blocks(&values, 0).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_delta_var_serde() -> Result<()> {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Posting {
+        term: String,
+        #[serde(with = "data_rw::delta_var")]
+        docs: Vec<u64>,
+    }
+
+    let posting = Posting {
+        term: "rust".to_string(),
+        docs: sorted(),
+    };
+    let mut data = Data::new();
+    data.serde_serialize(&posting)?;
+    let mut rd = DataReader::from(&data);
+    assert_eq!(rd.serde_deserialize::<Posting>()?, posting);
+    assert!(rd.is_empty());
+
+    let buf = bincode::serialize(&posting)?;
+    assert_eq!(bincode::deserialize::<Posting>(&buf)?, posting);
+    Ok(())
+}
+
+#[test]
+fn test_delta_var_truncated() -> Result<()> {
+    let values = sorted();
+    let mut data = Data::new();
+    data.write_delta_var_slice(&values);
+    let len = data.len();
+    data.truncate(len - 1);
+    assert!(DataReader::from(&data).read_delta_var_slice().is_err());
+
+    let mut data = Data::new();
+    data.write_delta_var_blocks(&values, 16)?;
+    let len = data.len();
+    data.truncate(len - 1);
+    assert!(DataReader::from(&data).read_delta_var_blocks().is_err());
+    Ok(())
+}
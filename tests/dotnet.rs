@@ -0,0 +1,134 @@
+use anyhow::Result;
+use data_rw::{Config, Data, DataReader};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[test]
+fn test_dotnet_7bit() -> Result<()> {
+    // bytes written by BinaryWriter.Write7BitEncodedInt/Write7BitEncodedInt64
+    let ints: [(i32, &[u8]); 6] = [
+        (0, &[0x00]),
+        (127, &[0x7f]),
+        (128, &[0x80, 0x01]),
+        (-1, &[0xff, 0xff, 0xff, 0xff, 0x0f]),
+        (i32::MAX, &[0xff, 0xff, 0xff, 0xff, 0x07]),
+        (i32::MIN, &[0x80, 0x80, 0x80, 0x80, 0x08]),
+    ];
+    for (v, expect) in ints {
+        let mut data = Data::new();
+        data.write_net_7bit_int(v);
+        assert_eq!(&data[..], expect);
+        assert_eq!(DataReader::from(expect).read_net_7bit_int()?, v);
+    }
+
+    let mut data = Data::new();
+    data.write_net_7bit_int64(-1);
+    let expect = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+    assert_eq!(&data[..], &expect);
+    assert_eq!(DataReader::from(&expect).read_net_7bit_int64()?, -1);
+
+    // the last byte of a 32 bit value has 4 bits, BinaryReader throws on more
+    assert!(DataReader::from(&[0xff, 0xff, 0xff, 0xff, 0x10])
+        .read_net_7bit_int()
+        .is_err());
+    assert!(DataReader::from(&expect[..9])
+        .read_net_7bit_int64()
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_dotnet_values() -> Result<()> {
+    let mut data = Data::new();
+    data.write_net_string("héllo")?;
+    data.write_net_char('é')?;
+    data.write_net_char('中')?;
+    assert!(data.write_net_char('🦀').is_err());
+    // 1.5m and -1.5m
+    data.write_net_decimal(15, 1)?;
+    data.write_net_decimal(-15, 1)?;
+    assert!(data.write_net_decimal(1 << 96, 0).is_err());
+    data.write_net_datetime(UNIX_EPOCH)?;
+    data.write_net_datetime(UNIX_EPOCH + Duration::from_millis(1))?;
+
+    let mut expect = vec![0x06, b'h', 0xc3, 0xa9, b'l', b'l', b'o'];
+    expect.extend_from_slice(&[0xc3, 0xa9, 0xe4, 0xb8, 0xad]);
+    expect.extend_from_slice(&[15, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]);
+    expect.extend_from_slice(&[15, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0x80]);
+    expect.extend_from_slice(&621_355_968_000_000_000i64.to_le_bytes());
+    expect.extend_from_slice(&621_355_968_000_010_000i64.to_le_bytes());
+    assert_eq!(&data[..], &expect[..]);
+
+    let mut rd = DataReader::from(&data);
+    assert_eq!(rd.read_net_string()?, "héllo");
+    assert_eq!(rd.read_net_char()?, 'é');
+    assert_eq!(rd.read_net_char()?, '中');
+    assert_eq!(rd.read_net_decimal()?, (15, 1));
+    assert_eq!(rd.read_net_decimal()?, (-15, 1));
+    assert_eq!(rd.read_net_datetime()?, UNIX_EPOCH);
+    assert_eq!(
+        rd.read_net_datetime()?,
+        UNIX_EPOCH + Duration::from_millis(1)
+    );
+    assert!(rd.is_empty());
+
+    // DateTime.MinValue
+    assert_eq!(
+        data_rw::dotnet::from_net_ticks(0)?,
+        UNIX_EPOCH - Duration::from_secs(62_135_596_800)
+    );
+    assert!(data_rw::dotnet::from_net_ticks(-1).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_dotnet_serde() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum Kind {
+        Idle,
+        Busy(u16),
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Player {
+        id: i32,
+        name: String,
+        initial: char,
+        alive: bool,
+        kind: Kind,
+        score: f32,
+        #[serde(with = "data_rw::dotnet::net_ticks")]
+        joined: SystemTime,
+    }
+
+    let player = Player {
+        id: -2,
+        name: "é".repeat(100),
+        initial: 'é',
+        alive: true,
+        kind: Kind::Busy(3),
+        score: 1.5,
+        joined: UNIX_EPOCH,
+    };
+
+    let config = Config::dotnet();
+    let data = Data::serialize_with(config, &player)?;
+
+    // writer.Write(-2); writer.Write(name); writer.Write('é'); writer.Write(true);
+    // writer.Write(1); writer.Write((ushort)3); writer.Write(1.5f); writer.Write(ticks);
+    let mut expect = Data::new();
+    expect.write_net_fixed(-2i32);
+    expect.write_net_string(&player.name)?;
+    expect.write_net_char('é')?;
+    expect.write_net_fixed(true);
+    expect.write_net_fixed(1u32);
+    expect.write_net_fixed(3u16);
+    expect.write_net_fixed(1.5f32);
+    expect.write_net_datetime(UNIX_EPOCH)?;
+    assert_eq!(&data[..], &expect[..]);
+    assert_eq!(&data[4..7], &[200, 1, 0xc3]);
+
+    assert_eq!(player, DataReader::deserialize_with(config, &data)?);
+    assert!(Data::serialize_with(config, '🦀').is_err());
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use anyhow::Result;
+use data_rw::{Data, DataReader};
+
+fn check_floats(values: &[f64]) -> Result<usize> {
+    let mut data = Data::new();
+    data.write_gorilla(values)?;
+    let size = data.len();
+    data.write_fixed(0x55u8);
+
+    let mut rd = DataReader::from(&data);
+    let mut count = 0;
+    for (v, expect) in rd.read_gorilla()?.zip(values) {
+        assert_eq!(v?.to_bits(), expect.to_bits());
+        count += 1;
+    }
+    assert_eq!(count, values.len());
+    assert_eq!(rd.read_fixed::<u8>()?, 0x55);
+    assert!(rd.is_empty());
+    Ok(size)
+}
+
+fn check_timestamps(values: &[i64]) -> Result<usize> {
+    let mut data = Data::new();
+    data.write_delta_of_delta(values)?;
+    let size = data.len();
+    data.write_fixed(0x55u8);
+
+    let mut rd = DataReader::from(&data);
+    let decoded = rd.read_delta_of_delta()?.collect::<Result<Vec<_>>>()?;
+    assert_eq!(decoded, values);
+    assert_eq!(rd.read_fixed::<u8>()?, 0x55);
+    assert!(rd.is_empty());
+    Ok(size)
+}
+
+#[test]
+fn test_gorilla() -> Result<()> {
+    assert_eq!(check_floats(&[])?, 1);
+    check_floats(&[1.5])?;
+    check_floats(&[
+        0.0,
+        -0.0,
+        f64::NAN,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::MIN_POSITIVE,
+        f64::MAX,
+        f64::MIN,
+        1e-300,
+        12.5,
+        12.5,
+        12.75,
+        -3.0,
+        f64::from_bits(1),
+        f64::from_bits(u64::MAX),
+    ])?;
+    check_floats(
+        &(0..1000)
+            .map(|i| (i as f64 * 0.1).sin())
+            .collect::<Vec<_>>(),
+    )?;
+
+    // a slowly changing gauge
+    let gauge = (0..1000)
+        .map(|i| 20.0 + (i / 100) as f64 * 0.5)
+        .collect::<Vec<_>>();
+    let size = check_floats(&gauge)?;
+    assert!(size * 10 < gauge.len() * 8, "{}", size);
+    Ok(())
+}
+
+#[test]
+fn test_delta_of_delta() -> Result<()> {
+    assert_eq!(check_timestamps(&[])?, 1);
+    check_timestamps(&[i64::MIN])?;
+    check_timestamps(&[
+        0,
+        i64::MAX,
+        i64::MIN,
+        -1,
+        63,
+        -1,
+        255,
+        -2048,
+        2047,
+        1 << 40,
+        1 << 40,
+        (1 << 40) + 1,
+    ])?;
+
+    // every bucket edge of the delta-of-delta
+    let mut values = vec![0i64];
+    let mut delta = 0;
+    for dod in [
+        0, -64, 63, -65, 64, -256, 255, -257, 256, -2048, 2047, -2049, 2048,
+    ] {
+        delta += dod;
+        values.push(values[values.len() - 1] + delta);
+    }
+    check_timestamps(&values)?;
+
+    // one sample a second with a little jitter
+    let times = (0..1000)
+        .map(|i| 1_600_000_000_000 + i * 1000 + if i % 50 == 0 { 3 } else { 0 })
+        .collect::<Vec<_>>();
+    let size = check_timestamps(&times)?;
+    assert!(size * 10 < times.len() * 8, "{}", size);
+    Ok(())
+}
+
+#[test]
+fn test_gorilla_truncated() -> Result<()> {
+    let mut data = Data::new();
+    data.write_gorilla(&[1.0, 2.0, 3.0])?;
+    let len = data.len();
+    data.truncate(len - 1);
+    let mut rd = DataReader::from(&data);
+    let res = rd.read_gorilla()?.collect::<Vec<_>>();
+    assert!(res.last().unwrap().is_err());
+
+    let mut data = Data::new();
+    data.write_delta_of_delta(&[1, 5, 1 << 50])?;
+    let len = data.len();
+    data.truncate(len - 2);
+    let mut rd = DataReader::from(&data);
+    assert!(rd.read_delta_of_delta()?.any(|v| v.is_err()));
+    Ok(())
+}
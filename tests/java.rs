@@ -0,0 +1,90 @@
+use anyhow::Result;
+use data_rw::{Config, Data, DataReader};
+use serde::{Deserialize, Serialize};
+
+#[test]
+fn test_java_values() -> Result<()> {
+    let mut data = Data::new();
+    data.write_java_fixed(-2i32);
+    data.write_java_fixed(1.5f64);
+    data.write_java_fixed(true);
+    data.write_java_char('é')?;
+    assert!(data.write_java_char('😀').is_err());
+    data.write_java_utf("A\0é😀")?;
+
+    // bytes written by DataOutputStream writeInt, writeDouble, writeBoolean, writeChar, writeUTF
+    let mut expect = vec![0xff, 0xff, 0xff, 0xfe];
+    expect.extend_from_slice(&[0x3f, 0xf8, 0, 0, 0, 0, 0, 0]);
+    expect.extend_from_slice(&[0x01, 0x00, 0xe9]);
+    expect.extend_from_slice(&[0x00, 0x0b, b'A', 0xc0, 0x80, 0xc3, 0xa9]);
+    expect.extend_from_slice(&[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]);
+    assert_eq!(&data[..], &expect[..]);
+
+    let mut rd = DataReader::from(&data);
+    assert_eq!(rd.read_java_fixed::<i32>()?, -2);
+    assert_eq!(rd.read_java_fixed::<f64>()?, 1.5);
+    assert!(rd.read_java_fixed::<bool>()?);
+    assert_eq!(rd.read_java_char()?, 'é');
+    assert_eq!(rd.read_java_utf()?, "A\0é😀");
+    assert!(rd.is_empty());
+
+    // a lone surrogate is valid Java but not a Rust string
+    let mut rd = DataReader::from(&[0x00, 0x03, 0xed, 0xa0, 0xbd]);
+    assert!(rd.read_java_utf().is_err());
+    let mut rd = DataReader::from(&[0x00, 0x02, 0xc3]);
+    assert!(rd.read_java_utf().is_err());
+
+    let mut data = Data::new();
+    assert!(data.write_java_utf(&"é".repeat(40000)).is_err());
+    assert!(data.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_java_serde() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum Kind {
+        Idle,
+        Busy(u16),
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Player {
+        id: i32,
+        name: String,
+        initial: char,
+        alive: bool,
+        kind: Kind,
+        scores: Vec<i64>,
+        ratio: f32,
+    }
+
+    let player = Player {
+        id: -2,
+        name: "null\0".to_string(),
+        initial: 'é',
+        alive: true,
+        kind: Kind::Busy(3),
+        scores: vec![1, -1],
+        ratio: 0.5,
+    };
+
+    let config = Config::java();
+    let data = Data::serialize_with(config, &player)?;
+
+    let mut expect = Data::new();
+    expect.write_java_fixed(-2i32);
+    expect.write_java_utf(&player.name)?;
+    expect.write_java_char('é')?;
+    expect.write_java_fixed(true);
+    expect.write_java_fixed(1i32);
+    expect.write_java_fixed(3u16);
+    expect.write_java_fixed(2i32);
+    expect.write_java_fixed(1i64);
+    expect.write_java_fixed(-1i64);
+    expect.write_java_fixed(0.5f32);
+    assert_eq!(&data[..], &expect[..]);
+
+    assert_eq!(player, DataReader::deserialize_with(config, &data)?);
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use data_rw::{Data, DataReader};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+/// `values` must be sorted, their keys must sort the same way and round-trip
+fn check_order<T>(values: &[T]) -> Result<()>
+where
+    T: Serialize + DeserializeOwned + Debug + PartialEq,
+{
+    let mut asc = vec![];
+    let mut desc = vec![];
+    for v in values {
+        let key = Data::serialize_key(v)?;
+        assert_eq!(&DataReader::deserialize_key::<T, _>(&key)?, v);
+        asc.push(key.into_inner());
+
+        let mut key = Data::new();
+        key.write_key(v, true)?;
+        let mut rd = DataReader::from(&key);
+        assert_eq!(&rd.read_key::<T>(true)?, v);
+        assert!(rd.is_empty());
+        desc.push(key.into_inner());
+    }
+    for i in 1..values.len() {
+        assert!(asc[i - 1] < asc[i], "{:?} < {:?}", values[i - 1], values[i]);
+        assert!(
+            desc[i - 1] > desc[i],
+            "{:?} > {:?}",
+            values[i - 1],
+            values[i]
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_key_scalars() -> Result<()> {
+    check_order(&[i64::MIN, -256, -1, 0, 1, 255, i64::MAX])?;
+    check_order(&[0u16, 1, 0xff, 0x100, u16::MAX])?;
+    check_order(&[i128::MIN, -1, 0, i128::MAX])?;
+    check_order(&[
+        f64::NEG_INFINITY,
+        -1e10,
+        -1.5,
+        -0.0,
+        0.0,
+        1e-300,
+        2.5,
+        f64::INFINITY,
+    ])?;
+    check_order(&[-3.5f32, -0.0, 0.0, 1.0])?;
+    check_order(&[false, true])?;
+    check_order(&['\0', 'a', 'é', '😀'])?;
+    check_order(&[None, Some(0u8), Some(1)])?;
+
+    let strings = ["", "\0", "\0\0", "\0a", "a", "a\0", "a\0b", "ab", "b", "é"];
+    check_order(&strings.iter().map(|s| s.to_string()).collect::<Vec<_>>())?;
+    assert_eq!(&Data::serialize_key("a\0")?[..], &[b'a', 0, 0xff, 0, 1]);
+    assert_eq!(&Data::serialize_key(-1i16)?[..], &[0x7f, 0xff]);
+    Ok(())
+}
+
+#[test]
+fn test_key_compound() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, PartialOrd)]
+    enum Kind {
+        User(String),
+        Group { id: u32, sub: Option<u16> },
+        Any,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, PartialOrd)]
+    struct Key {
+        tenant: u16,
+        kind: Kind,
+        path: Vec<String>,
+        time: (i64, u8),
+    }
+
+    let key = |tenant, kind, path: &[&str], time| Key {
+        tenant,
+        kind,
+        path: path.iter().map(|s| s.to_string()).collect(),
+        time,
+    };
+    let keys = vec![
+        key(1, Kind::User("a".into()), &[], (0, 0)),
+        key(1, Kind::User("a".into()), &[""], (0, 0)),
+        key(1, Kind::User("a".into()), &["", ""], (-5, 0)),
+        key(1, Kind::User("a".into()), &["a"], (0, 0)),
+        key(1, Kind::User("ab".into()), &[], (0, 0)),
+        key(1, Kind::Group { id: 2, sub: None }, &[], (0, 0)),
+        key(
+            1,
+            Kind::Group {
+                id: 2,
+                sub: Some(0),
+            },
+            &[],
+            (-1, 9),
+        ),
+        key(
+            1,
+            Kind::Group {
+                id: 2,
+                sub: Some(0),
+            },
+            &[],
+            (0, 0),
+        ),
+        key(1, Kind::Any, &["x"], (0, 0)),
+        key(2, Kind::User("".into()), &[], (i64::MIN, 0)),
+    ];
+    for pair in keys.windows(2) {
+        assert!(pair[0] < pair[1]);
+    }
+    check_order(&keys)?;
+
+    let maps = vec![
+        BTreeMap::new(),
+        vec![(1u8, 2u8)].into_iter().collect(),
+        vec![(1, 2), (2, 0)].into_iter().collect(),
+        vec![(1, 3)].into_iter().collect(),
+        vec![(2, 0)].into_iter().collect::<BTreeMap<_, _>>(),
+    ];
+    check_order(&maps)?;
+
+    // an ascending part followed by a descending one
+    let mut a = Data::new();
+    a.write_key("user", false)?;
+    a.write_key(5u64, true)?;
+    let mut b = Data::new();
+    b.write_key("user", false)?;
+    b.write_key(3u64, true)?;
+    assert!(a[..] < b[..]);
+    let mut rd = DataReader::from(&a);
+    assert_eq!(rd.read_key::<String>(false)?, "user");
+    assert_eq!(rd.read_key::<u64>(true)?, 5);
+
+    #[derive(Serialize)]
+    struct Sparse {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        a: Option<u32>,
+        b: u32,
+    }
+    assert!(Data::serialize_key(Sparse { a: None, b: 7 }).is_err());
+    assert!(Data::serialize_key(Sparse { a: Some(1), b: 7 }).is_ok());
+
+    assert!(DataReader::deserialize_key::<String, _>(&[b'a', 0, 2]).is_err());
+    assert!(DataReader::deserialize_key::<Vec<u8>, _>(&[2]).is_err());
+    Ok(())
+}
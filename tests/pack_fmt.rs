@@ -0,0 +1,95 @@
+use anyhow::Result;
+use data_rw::pack_fmt::{calcsize, FmtValue};
+use data_rw::{pack_fmt, unpack_fmt, Data, DataReader};
+
+#[test]
+fn test_pack_fmt() -> Result<()> {
+    let mut data = Data::new();
+    data.pack_fmt(
+        "<IHh 4s",
+        &[1u32.into(), 2u16.into(), (-3i16).into(), "ab".into()],
+    )?;
+    // python: struct.pack("<IHh4s", 1, 2, -3, b"ab")
+    assert_eq!(&data[..], &[1, 0, 0, 0, 2, 0, 0xfd, 0xff, b'a', b'b', 0, 0]);
+
+    let mut data = Data::new();
+    data.pack_fmt(
+        ">2Bx?d3s",
+        &[
+            1u8.into(),
+            2u8.into(),
+            true.into(),
+            1.5f64.into(),
+            "abcd".into(),
+        ],
+    )?;
+    let mut expect = vec![1, 2, 0, 1, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0];
+    expect.extend_from_slice(b"abc");
+    assert_eq!(&data[..], &expect[..]);
+
+    // native mode aligns numbers to their size
+    let mut data = Data::new();
+    data.pack_fmt("@cIq", &[b"z"[..].into(), 7u32.into(), (-1i64).into()])?;
+    assert_eq!(data.len(), 16);
+    assert_eq!(calcsize("@cIq")?, 16);
+    assert_eq!(calcsize("=cIq")?, 13);
+    assert_eq!(calcsize("!c0i")?, 1);
+    assert_eq!(calcsize("c0i")?, 4);
+
+    let mut rd = DataReader::from(&data);
+    assert_eq!(
+        rd.unpack_fmt("cIq")?,
+        vec![
+            FmtValue::Bytes(b"z".to_vec()),
+            FmtValue::UInt(7),
+            FmtValue::Int(-1)
+        ]
+    );
+    assert!(rd.is_empty());
+
+    let mut rd = DataReader::from(&[1, 2, 3, 4, 0x40, 0x20, 0, 0]);
+    assert_eq!(
+        rd.unpack_fmt("!hxBf")?,
+        vec![
+            FmtValue::Int(0x102),
+            FmtValue::UInt(4),
+            FmtValue::Float(2.5)
+        ]
+    );
+
+    // errors leave the data untouched
+    let mut data = Data::new();
+    assert!(data.pack_fmt("<B", &[256u32.into()]).is_err());
+    assert!(data.pack_fmt("<b", &[(-1i32).into(), 1u8.into()]).is_err());
+    assert!(data.pack_fmt("<BB", &[1u8.into()]).is_err());
+    assert!(data.pack_fmt("<?", &[1u8.into()]).is_err());
+    assert!(data.pack_fmt("<z", &[]).is_err());
+    assert!(data.pack_fmt("<3", &[]).is_err());
+    assert!(data.is_empty());
+    assert!(DataReader::from(&[1, 2, 3]).unpack_fmt("<I").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_pack_fmt_macro() -> Result<()> {
+    let mut data = Data::new();
+    pack_fmt!(data, "<IHh4s", 1u32, 2u16, -3i16, *b"abcd")?;
+    pack_fmt!(data, ">2Bxc?", 1u8, 2u8, b'z', true)?;
+    pack_fmt!(data, "<")?;
+    assert_eq!(
+        &data[..],
+        &[1, 0, 0, 0, 2, 0, 0xfd, 0xff, b'a', b'b', b'c', b'd', 1, 2, 0, b'z', 1]
+    );
+
+    let mut rd = DataReader::from(&data);
+    let (a, b, c, d): (u32, u16, i16, [u8; 4]) = unpack_fmt!(rd, "<IHh4s")?;
+    assert_eq!((a, b, c, &d), (1, 2, -3, b"abcd"));
+    let (e, f, g, h): (u8, u8, u8, bool) = unpack_fmt!(rd, ">2Bxc?")?;
+    assert_eq!((e, f, g, h), (1, 2, b'z', true));
+    assert!(rd.is_empty());
+
+    let rd = &mut DataReader::from(&[0, 0, 0, 0, 0, 0, 0xf0, 0x3f]);
+    let (v,): (f64,) = unpack_fmt!(*rd, "<d")?;
+    assert_eq!(v, 1.0);
+    Ok(())
+}
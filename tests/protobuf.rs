@@ -0,0 +1,173 @@
+use anyhow::Result;
+use data_rw::protobuf::WireType;
+use data_rw::{Data, DataReader};
+
+#[test]
+fn test_protobuf_write() -> Result<()> {
+    let mut data = Data::new();
+    // message Test1 { int32 a = 1; } a = 150
+    data.write_tag(1, WireType::Varint)?;
+    data.write_var_integer(150u32);
+    // string b = 2; b = "testing"
+    data.write_tag(2, WireType::LengthDelimited)?;
+    data.write_var_integer("testing");
+    // Test1 c = 3;
+    data.write_tag(3, WireType::LengthDelimited)?;
+    data.write_message(|msg| {
+        msg.write_tag(1, WireType::Varint)?;
+        msg.write_var_integer(150u32);
+        Ok(())
+    })?;
+    // sint32 d = 4; d = -1
+    data.write_tag(4, WireType::Varint)?;
+    data.write_var_integer(-1i32);
+    // fixed32 e = 5; sfixed64 f = 6
+    data.write_tag(5, WireType::Fixed32)?;
+    data.write_fixed32(1);
+    data.write_tag(6, WireType::Fixed64)?;
+    data.write_fixed64(-2i64 as u64);
+
+    let mut expect = vec![0x08, 0x96, 0x01];
+    expect.extend_from_slice(&[0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g']);
+    expect.extend_from_slice(&[0x1a, 0x03, 0x08, 0x96, 0x01]);
+    expect.extend_from_slice(&[0x20, 0x01]);
+    expect.extend_from_slice(&[0x2d, 1, 0, 0, 0]);
+    expect.extend_from_slice(&[0x31, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+    assert_eq!(&data[..], &expect[..]);
+
+    assert!(data.write_tag(0, WireType::Varint).is_err());
+    assert!(data.write_tag(1 << 29, WireType::Varint).is_err());
+
+    // a sub-message longer than 127 bytes needs a 2 byte length
+    let mut data = Data::new();
+    data.write_message(|msg| {
+        msg.write_tag(1, WireType::LengthDelimited)?;
+        msg.write_var_integer(&[7u8; 200][..]);
+        Ok(())
+    })?;
+    assert_eq!(&data[..5], &[0xcb, 0x01, 0x0a, 0xc8, 0x01]);
+    assert_eq!(data.len(), 205);
+    Ok(())
+}
+
+#[test]
+fn test_protobuf_read() -> Result<()> {
+    let mut buff = vec![0x08, 0x96, 0x01];
+    // unknown fields: a group holding a varint, a fixed32 and a string
+    buff.extend_from_slice(&[0x3b, 0x08, 0x01, 0x3c]);
+    buff.extend_from_slice(&[0x45, 1, 2, 3, 4]);
+    buff.extend_from_slice(&[0x4a, 0x02, b'h', b'i']);
+    buff.extend_from_slice(&[0x1a, 0x03, 0x08, 0x96, 0x01]);
+    buff.extend_from_slice(&[0x31, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+
+    let mut rd = DataReader::from(&buff);
+    let (mut a, mut c, mut f) = (0u32, 0u32, 0i64);
+    while !rd.is_empty() {
+        match rd.read_tag()? {
+            (1, WireType::Varint) => a = rd.read_var_integer()?,
+            (3, WireType::LengthDelimited) => {
+                let mut msg = rd.read_message()?;
+                while !msg.is_empty() {
+                    match msg.read_tag()? {
+                        (1, WireType::Varint) => c = msg.read_var_integer()?,
+                        (field, wire_type) => msg.skip_field(field, wire_type)?,
+                    }
+                }
+            }
+            (6, WireType::Fixed64) => f = rd.read_fixed64()? as i64,
+            (field, wire_type) => rd.skip_field(field, wire_type)?,
+        }
+    }
+    assert_eq!((a, c, f), (150, 150, -2));
+
+    let mut rd = DataReader::from(&[0x45, 1, 2, 3, 4]);
+    rd.read_tag()?;
+    assert_eq!(rd.read_fixed32()?, 0x04030201);
+
+    assert!(DataReader::from(&[0x0e]).read_tag().is_err());
+    assert!(DataReader::from(&[0x00]).read_tag().is_err());
+    assert!(DataReader::from(&[0x3c])
+        .skip_field(7, WireType::EndGroup)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_protobuf_skip_groups() -> Result<()> {
+    // group 7 holding group 8, then a varint field
+    let buff = [0x3b, 0x43, 0x08, 0x01, 0x44, 0x3c, 0x08, 0x05];
+    let mut rd = DataReader::from(&buff);
+    let (field, wire_type) = rd.read_tag()?;
+    rd.skip_field(field, wire_type)?;
+    assert_eq!(rd.read_tag()?, (1, WireType::Varint));
+    assert_eq!(rd.read_var_integer::<u32>()?, 5);
+
+    // group 7 closed by the end group of field 8
+    let mut rd = DataReader::from(&[0x3b, 0x08, 0x01, 0x44]);
+    let (field, wire_type) = rd.read_tag()?;
+    assert!(rd.skip_field(field, wire_type).is_err());
+
+    // nesting is limited instead of exhausting the stack
+    let mut data = Data::new();
+    for _ in 0..data_rw::protobuf::MAX_GROUP_DEPTH + 1 {
+        data.write_tag(1, WireType::StartGroup)?;
+    }
+    for _ in 0..data_rw::protobuf::MAX_GROUP_DEPTH + 1 {
+        data.write_tag(1, WireType::EndGroup)?;
+    }
+    let mut rd = DataReader::from(&data);
+    let (field, wire_type) = rd.read_tag()?;
+    assert!(rd.skip_field(field, wire_type).is_err());
+
+    let mut rd = DataReader::from(&data[1..data.len() - 1]);
+    let (field, wire_type) = rd.read_tag()?;
+    rd.skip_field(field, wire_type)?;
+    assert!(rd.is_empty());
+
+    let mut rd = DataReader::from(&[0x0b; 100_000][..]);
+    let (field, wire_type) = rd.read_tag()?;
+    assert!(rd.skip_field(field, wire_type).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_protobuf_write_rollback() -> Result<()> {
+    let mut data = Data::new();
+    data.write_tag(1, WireType::Varint)?;
+    data.write_var_integer(1u32);
+    data.write_tag(2, WireType::LengthDelimited)?;
+    let res = data.write_message(|msg| {
+        msg.write_tag(1, WireType::Varint)?;
+        msg.write_var_integer(150u32);
+        // field number 0 is invalid
+        msg.write_tag(0, WireType::Varint)
+    });
+    assert!(res.is_err());
+    assert_eq!(&data[..], &[0x08, 0x01, 0x12]);
+
+    // a nested message written after the failed one gets its real length
+    data.write_message(|msg| {
+        msg.write_tag(3, WireType::LengthDelimited)?;
+        msg.write_message(|msg| {
+            msg.write_tag(1, WireType::LengthDelimited)?;
+            msg.write_var_integer("0123456789");
+            Ok(())
+        })
+    })?;
+    assert_eq!(&data[3..6], &[14, 0x1a, 12]);
+    assert_eq!(data.len(), 3 + 15);
+
+    let mut rd = DataReader::from(&data);
+    assert_eq!(rd.read_tag()?, (1, WireType::Varint));
+    assert_eq!(rd.read_var_integer::<u32>()?, 1);
+    assert_eq!(rd.read_tag()?, (2, WireType::LengthDelimited));
+    let mut msg = rd.read_message()?;
+    assert!(rd.is_empty());
+    assert_eq!(msg.read_tag()?, (3, WireType::LengthDelimited));
+    let mut inner = msg.read_message()?;
+    assert!(msg.is_empty());
+    assert_eq!(inner.read_tag()?, (1, WireType::LengthDelimited));
+    assert_eq!(inner.read_var_str()?, "0123456789");
+    assert!(inner.is_empty());
+    Ok(())
+}
@@ -0,0 +1,779 @@
+use anyhow::Result;
+use data_rw::serde::error::DataError;
+use data_rw::{Config, Data, DataOwnedReader, DataReader};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+
+#[test]
+pub fn test_skippable_fields() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Inner {
+        x: i32,
+        y: String,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct V1 {
+        id: u32,
+        name: String,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct V2 {
+        id: u32,
+        #[serde(default)]
+        tags: Vec<String>,
+        name: String,
+        #[serde(default)]
+        inner: Option<Inner>,
+    }
+
+    let config = Config::new().skippable_fields(true);
+    let v2 = V2 {
+        id: 1,
+        tags: vec!["a".to_string(), "b".to_string()],
+        name: "test".to_string(),
+        inner: Some(Inner {
+            x: 5,
+            y: "inner".to_string(),
+        }),
+    };
+    let v1 = V1 {
+        id: 2,
+        name: "old".to_string(),
+    };
+
+    let mut data = Data::new();
+    data.set_config(config);
+    data.serde_serialize(&v2)?;
+    data.serde_serialize(&v1)?;
+    data.serde_serialize(&v2)?;
+
+    {
+        let mut rdata = DataReader::from(&data[..]);
+        rdata.set_config(config);
+        assert_eq!(
+            V1 {
+                id: 1,
+                name: "test".to_string()
+            },
+            rdata.serde_deserialize::<V1>()?
+        );
+        assert_eq!(
+            V2 {
+                id: 2,
+                tags: vec![],
+                name: "old".to_string(),
+                inner: None
+            },
+            rdata.serde_deserialize::<V2>()?
+        );
+        assert_eq!(v2, rdata.serde_deserialize::<V2>()?);
+        assert!(rdata.is_empty());
+    }
+    {
+        let mut rdata = DataOwnedReader::new(data.to_vec());
+        rdata.set_config(config);
+        assert_eq!(1, rdata.serde_deserialize::<V1>()?.id);
+        assert_eq!(2, rdata.serde_deserialize::<V2>()?.id);
+        assert_eq!(v2, rdata.serde_deserialize::<V2>()?);
+    }
+    {
+        // without the config unknown fields are still an error
+        let mut data = Data::new();
+        data.serde_serialize(&v2)?;
+        let mut rdata = DataReader::from(&data[..]);
+        assert!(rdata.serde_deserialize::<V1>().is_err());
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum E1 {
+        S { r: u8 },
+    }
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum E2 {
+        S {
+            r: u8,
+            #[serde(default)]
+            g: u8,
+        },
+    }
+
+    let mut data = Data::new();
+    data.set_config(config);
+    data.serde_serialize(E2::S { r: 1, g: 2 })?;
+    data.serde_serialize(E1::S { r: 3 })?;
+    let mut rdata = DataReader::from(&data[..]);
+    rdata.set_config(config);
+    assert_eq!(E1::S { r: 1 }, rdata.serde_deserialize::<E1>()?);
+    assert_eq!(E2::S { r: 3, g: 0 }, rdata.serde_deserialize::<E2>()?);
+
+    // only an unknown field is skipped as a whole, a value ignored
+    // inside a field doesn't swallow the rest of the field
+    #[derive(Serialize)]
+    struct Pair {
+        pair: (u32, u32),
+        id: u8,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Ignored {
+        #[allow(dead_code)]
+        pair: (IgnoredAny, IgnoredAny),
+    }
+
+    let mut data = Data::new();
+    data.set_config(config);
+    data.serde_serialize(Pair {
+        pair: (1, 2),
+        id: 3,
+    })?;
+    let mut rdata = DataReader::from(&data[..]);
+    rdata.set_config(config);
+    assert!(matches!(
+        rdata.serde_deserialize::<Ignored>(),
+        Err(DataError::IgnoredAnyNotSupported)
+    ));
+    Ok(())
+}
+
+#[test]
+pub fn test_tagged() -> Result<()> {
+    use std::collections::BTreeMap;
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum E {
+        A,
+        B(u8),
+        C(String, i64),
+        D { x: f32, y: Option<bool> },
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    #[serde(untagged)]
+    enum Untagged {
+        Num(i64),
+        Text(String),
+        List(Vec<u32>),
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    #[serde(tag = "type")]
+    enum Internal {
+        Login { user: String },
+        Logout,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    #[serde(tag = "t", content = "c")]
+    enum Adjacent {
+        Ping(u64),
+        Pong,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Base {
+        id: u32,
+        name: String,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Flatten {
+        #[serde(flatten)]
+        base: Base,
+        #[serde(flatten)]
+        extra: BTreeMap<String, u32>,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Foo {
+        e: Vec<E>,
+        u: Vec<Untagged>,
+        i: Vec<Internal>,
+        a: Vec<Adjacent>,
+        f: Flatten,
+        t: (char, (), Option<u128>),
+        b: Vec<u8>,
+    }
+
+    let mut extra = BTreeMap::new();
+    extra.insert("x".to_string(), 1);
+    extra.insert("y".to_string(), 2);
+    let foo = Foo {
+        e: vec![
+            E::A,
+            E::B(1),
+            E::C("c".to_string(), -5),
+            E::D {
+                x: 0.5,
+                y: Some(true),
+            },
+        ],
+        u: vec![
+            Untagged::Num(-1),
+            Untagged::Text("text".to_string()),
+            Untagged::List(vec![1, 2]),
+        ],
+        i: vec![
+            Internal::Login {
+                user: "root".to_string(),
+            },
+            Internal::Logout,
+        ],
+        a: vec![Adjacent::Ping(5), Adjacent::Pong],
+        f: Flatten {
+            base: Base {
+                id: 7,
+                name: "base".to_string(),
+            },
+            extra,
+        },
+        t: ('你', (), Some(u128::MAX)),
+        b: vec![1, 2, 3],
+    };
+
+    let config = Config::new().tagged(true);
+    let mut data = Data::new();
+    data.set_config(config);
+    data.serde_serialize(&foo)?;
+    data.serde_serialize(E::A)?;
+    data.serde_serialize(1u8)?;
+
+    let mut rdata = DataReader::from(&data[..]);
+    rdata.set_config(config);
+    assert_eq!(foo, rdata.serde_deserialize::<Foo>()?);
+    assert_eq!(E::A, rdata.serde_deserialize::<E>()?);
+    assert_eq!(1u8, rdata.serde_deserialize::<u8>()?);
+    assert!(rdata.is_empty());
+
+    // the same bytes decoded without knowing the type
+    let json = serde_json::json!({
+        "id": 1,
+        "list": [1, -2, 3.5, "four", null, true],
+        "map": {"a": {"b": []}},
+    });
+    let mut data = Data::new();
+    data.set_config(config);
+    data.serde_serialize(&json)?;
+    let mut rdata = DataReader::from(&data[..]);
+    rdata.set_config(config);
+    assert_eq!(json, rdata.serde_deserialize::<serde_json::Value>()?);
+
+    let mut data = Data::new();
+    data.set_config(config);
+    data.serde_serialize(&foo.e)?;
+    let mut rdata = DataReader::from(&data[..]);
+    rdata.set_config(config);
+    assert_eq!(
+        serde_json::json!(["A", {"B": 1}, {"C": ["c", -5]}, {"D": {"x": 0.5, "y": true}}]),
+        rdata.serde_deserialize::<serde_json::Value>()?
+    );
+    Ok(())
+}
+
+#[test]
+pub fn test_unit_variant() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum E {
+        A,
+        B(u8),
+    }
+    let mut data = Data::new();
+    data.serde_serialize(E::A)?;
+    data.serde_serialize(E::B(3))?;
+    let mut rdata = DataReader::from(&data[..]);
+    assert_eq!(E::A, rdata.serde_deserialize::<E>()?);
+    assert_eq!(E::B(3), rdata.serde_deserialize::<E>()?);
+    Ok(())
+}
+
+#[test]
+pub fn test_positional_structs() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Rgb {
+        r: u8,
+        g: u8,
+        b: u8,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum E {
+        S { r: u8, name: String },
+        T(u8),
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Foo {
+        rgb: Rgb,
+        e: Vec<E>,
+        o: Option<Rgb>,
+    }
+
+    let config = Config::new().positional_structs(true);
+    let rgb = Rgb { r: 1, g: 2, b: 3 };
+    let mut data = Data::new();
+    data.set_config(config);
+    data.serde_serialize(&rgb)?;
+    assert_eq!(&data[..], &[1, 2, 3]);
+
+    let foo = Foo {
+        rgb: rgb.clone(),
+        e: vec![
+            E::S {
+                r: 4,
+                name: "s".to_string(),
+            },
+            E::T(5),
+        ],
+        o: Some(rgb.clone()),
+    };
+
+    for config in [config, config.tagged(true)] {
+        let mut data = Data::new();
+        data.set_config(config);
+        data.serde_serialize(&foo)?;
+        data.serde_serialize(&rgb)?;
+        let mut rdata = DataReader::from(&data[..]);
+        rdata.set_config(config);
+        assert_eq!(foo, rdata.serde_deserialize::<Foo>()?);
+        assert_eq!(rgb, rdata.serde_deserialize::<Rgb>()?);
+        assert!(rdata.is_empty());
+    }
+
+    #[derive(Serialize)]
+    struct Skip {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        a: Option<u8>,
+    }
+    let mut data = Data::new();
+    data.set_config(config);
+    assert!(data.serde_serialize(Skip { a: None }).is_err());
+    Ok(())
+}
+
+#[test]
+pub fn test_variant_index() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum Event {
+        Start,
+        Move(i32, i32),
+        Say(String),
+        Stop { code: u8 },
+    }
+
+    let events = vec![
+        Event::Start,
+        Event::Move(1, -1),
+        Event::Say("hi".to_string()),
+        Event::Stop { code: 9 },
+    ];
+
+    let config = Config::new().variant_index(true);
+    let mut data = Data::new();
+    data.set_config(config);
+    data.serde_serialize(Event::Start)?;
+    data.serde_serialize(Event::Say("hi".to_string()))?;
+    let expect: &[u8] = if cfg!(feature = "big_endian") {
+        &[0, 2, 0, 0, 0, 2, b'h', b'i']
+    } else {
+        &[0, 2, 2, 0, 0, 0, b'h', b'i']
+    };
+    assert_eq!(&data[..], expect);
+
+    for config in [
+        config,
+        config.tagged(true),
+        config.positional_structs(true),
+        config.tagged(true).positional_structs(true),
+    ] {
+        let mut data = Data::new();
+        data.set_config(config);
+        data.serde_serialize(&events)?;
+        data.serde_serialize(Event::Start)?;
+        let mut rdata = DataReader::from(&data[..]);
+        rdata.set_config(config);
+        assert_eq!(events, rdata.serde_deserialize::<Vec<Event>>()?);
+        assert_eq!(Event::Start, rdata.serde_deserialize::<Event>()?);
+        assert!(rdata.is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+pub fn test_len_int_encoding() -> Result<()> {
+    use data_rw::{IntEncoding, LenEncoding};
+    use std::collections::BTreeMap;
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Row {
+        id: u64,
+        delta: i32,
+        small: u16,
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        tags: Vec<String>,
+        c: char,
+    }
+
+    let row = Row {
+        id: 7,
+        delta: -3,
+        small: 300,
+        name: "x".repeat(200),
+        note: None,
+        tags: vec!["a".to_string(), "b".to_string()],
+        c: 'é',
+    };
+
+    let config = Config::new()
+        .len_encoding(LenEncoding::Varint)
+        .int_encoding(IntEncoding::Varint);
+    let data = Data::serialize_with(config, 1u32)?;
+    assert_eq!(&data[..], &[1]);
+    let data = Data::serialize_with(config, -1i64)?;
+    assert_eq!(&data[..], &[1]);
+    let data = Data::serialize_with(config, "hi")?;
+    assert_eq!(&data[..], &[2, b'h', b'i']);
+
+    let fixed = Data::serialize(&row)?;
+    for config in [
+        config,
+        config.skippable_fields(true),
+        config.tagged(true),
+        Config::new().len_encoding(LenEncoding::Varint),
+        Config::new().int_encoding(IntEncoding::Varint),
+    ] {
+        let data = Data::serialize_with(config, &row)?;
+        assert_eq!(row, DataReader::deserialize_with::<Row, _>(config, &data)?);
+    }
+    let data = Data::serialize_with(config, &row)?;
+    assert!(data.len() < fixed.len());
+
+    // unknown lengths are reserved and patched, the varint may grow
+    let map = (0..200u32)
+        .map(|i| (i, i.to_string()))
+        .collect::<BTreeMap<_, _>>();
+    let iter = Unsized(map.clone());
+    for config in [config, config.skippable_fields(true)] {
+        let data = Data::serialize_with(config, &iter)?;
+        let value: BTreeMap<u32, String> = DataReader::deserialize_with(config, &data)?;
+        assert_eq!(map, value);
+    }
+    Ok(())
+}
+
+struct Unsized(std::collections::BTreeMap<u32, String>);
+
+impl Serialize for Unsized {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().filter(|_| true))
+    }
+}
+
+#[test]
+pub fn test_human_readable() -> Result<()> {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let data = Data::serialize(ip)?;
+    assert_eq!(&data[..], &Data::serialize("127.0.0.1")?[..]);
+
+    let config = Config::new().human_readable(false);
+    let data = Data::serialize_with(config, ip)?;
+    assert_ne!(&data[..], &Data::serialize("127.0.0.1")?[..]);
+    assert_eq!(
+        ip,
+        DataReader::deserialize_with::<IpAddr, _>(config, &data)?
+    );
+    Ok(())
+}
+
+#[test]
+pub fn test_len_overflow() -> Result<()> {
+    use data_rw::LenEncoding;
+
+    struct Huge;
+
+    impl Serialize for Huge {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeSeq;
+            serializer.serialize_seq(Some(u32::MAX as usize + 1))?.end()
+        }
+    }
+
+    assert!(Data::serialize(Huge).is_err());
+    let config = Config::new().len_encoding(LenEncoding::U64);
+    let data = Data::serialize_with(config, Huge)?;
+    let len = u32::MAX as u64 + 1;
+    let expect = if cfg!(feature = "big_endian") {
+        len.to_be_bytes()
+    } else {
+        len.to_le_bytes()
+    };
+    assert_eq!(&data[..], &expect);
+
+    let value = (vec!["a".to_string(); 3], Some(b'c' as char));
+    for config in [config, config.tagged(true)] {
+        let data = Data::serialize_with(config, &value)?;
+        assert_eq!(value, DataReader::deserialize_with(config, &data)?);
+    }
+    Ok(())
+}
+
+#[test]
+pub fn test_field_adapters() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Packet {
+        #[serde(with = "data_rw::varint")]
+        id: u64,
+        #[serde(with = "data_rw::varint")]
+        deltas: Vec<i32>,
+        #[serde(with = "data_rw::u16_len_str")]
+        name: String,
+        #[serde(with = "data_rw::fixed_array")]
+        hash: [u8; 40],
+        #[serde(with = "data_rw::fixed_array")]
+        pairs: [(u8, u8); 2],
+        plain: u32,
+    }
+
+    let packet = Packet {
+        id: 300,
+        deltas: vec![-1, 1],
+        name: "ab".to_string(),
+        hash: [7; 40],
+        pairs: [(1, 2), (3, 4)],
+        plain: 5,
+    };
+
+    let config = Config::new().positional_structs(true);
+    let data = Data::serialize_with(config, &packet)?;
+    let mut expect = if cfg!(feature = "big_endian") {
+        vec![0xac, 0x02, 0, 0, 0, 2, 1, 2, 0, 2, b'a', b'b']
+    } else {
+        vec![0xac, 0x02, 2, 0, 0, 0, 1, 2, 2, 0, b'a', b'b']
+    };
+    expect.extend_from_slice(&[7; 40]);
+    if cfg!(feature = "big_endian") {
+        expect.extend_from_slice(&[0, 0, 0, 2, 1, 2, 0, 0, 0, 2, 3, 4]);
+        expect.extend_from_slice(&[0, 0, 0, 5]);
+    } else {
+        expect.extend_from_slice(&[2, 0, 0, 0, 1, 2, 2, 0, 0, 0, 3, 4]);
+        expect.extend_from_slice(&[5, 0, 0, 0]);
+    }
+    assert_eq!(&data[..], &expect[..]);
+
+    for config in [
+        Config::new(),
+        config,
+        Config::new().skippable_fields(true),
+        Config::new().tagged(true),
+    ] {
+        let data = Data::serialize_with(config, &packet)?;
+        assert_eq!(packet, DataReader::deserialize_with(config, &data)?);
+    }
+
+    // other formats see plain values
+    let json = serde_json::to_string(&packet)?;
+    assert_eq!(packet, serde_json::from_str(&json)?);
+
+    #[derive(Serialize)]
+    struct Long {
+        #[serde(with = "data_rw::u16_len_str")]
+        name: String,
+    }
+    assert!(Data::serialize(Long {
+        name: "x".repeat(u16::MAX as usize + 1)
+    })
+    .is_err());
+    Ok(())
+}
+
+#[test]
+pub fn test_fixed_tuples() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Point(i16, i16);
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum Shape {
+        Line(Point, Point),
+        Dot(Point),
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Mesh {
+        vertices: Vec<[f32; 3]>,
+        pair: (u8, String),
+        shapes: Vec<Shape>,
+    }
+
+    let config = Config::new().fixed_tuples(true);
+    let data = Data::serialize_with(config, [1f32, 2f32, 3f32])?;
+    assert_eq!(data.len(), 12);
+    let data = Data::serialize_with(config, Point(1, -1))?;
+    let expect = if cfg!(feature = "big_endian") {
+        [0, 1, 0xff, 0xff]
+    } else {
+        [1, 0, 0xff, 0xff]
+    };
+    assert_eq!(&data[..], &expect);
+
+    let mesh = Mesh {
+        vertices: vec![[0.0, 1.0, 2.0], [3.0, 4.0, 5.0]],
+        pair: (1, "a".to_string()),
+        shapes: vec![
+            Shape::Line(Point(0, 0), Point(1, 1)),
+            Shape::Dot(Point(2, 2)),
+        ],
+    };
+    for config in [
+        config,
+        config.positional_structs(true).variant_index(true),
+        config.tagged(true),
+    ] {
+        let data = Data::serialize_with(config, &mesh)?;
+        assert_eq!(mesh, DataReader::deserialize_with(config, &data)?);
+    }
+    assert!(Data::serialize_with(config, &mesh)?.len() < Data::serialize(&mesh)?.len());
+    Ok(())
+}
+
+#[test]
+pub fn test_bincode() -> Result<()> {
+    use std::collections::BTreeMap;
+    use std::net::{IpAddr, Ipv6Addr};
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Marker;
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Pair(u8, i64);
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum Event {
+        Start,
+        Move(i32, i32),
+        Say(String),
+        Stop { code: u8, reason: Option<String> },
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Record {
+        flag: bool,
+        small: i8,
+        wide: u128,
+        ratio: f64,
+        letters: Vec<char>,
+        name: String,
+        #[serde(with = "serde_bytes_compat")]
+        blob: Vec<u8>,
+        unit: (),
+        marker: Marker,
+        pair: Pair,
+        array: [u16; 4],
+        events: Vec<Event>,
+        map: BTreeMap<String, u32>,
+        ip: IpAddr,
+        missing: Option<Box<Record>>,
+    }
+
+    mod serde_bytes_compat {
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(v)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+            <&[u8]>::deserialize(d).map(|v| v.to_vec())
+        }
+    }
+
+    let record = Record {
+        flag: true,
+        small: -2,
+        wide: u128::MAX - 1,
+        ratio: 0.5,
+        letters: vec!['a', 'é', '中', '🦀'],
+        name: "bincode".to_string(),
+        blob: vec![1, 2, 3],
+        unit: (),
+        marker: Marker,
+        pair: Pair(1, -1),
+        array: [1, 2, 3, 4],
+        events: vec![
+            Event::Start,
+            Event::Move(1, -1),
+            Event::Say("hi".to_string()),
+            Event::Stop {
+                code: 1,
+                reason: None,
+            },
+        ],
+        map: vec![("a".to_string(), 1), ("b".to_string(), 2)]
+            .into_iter()
+            .collect(),
+        ip: IpAddr::V6(Ipv6Addr::LOCALHOST),
+        missing: None,
+    };
+
+    let config = Config::bincode();
+    let data = Data::serialize_with(config, &record)?;
+    let expect = bincode::serialize(&record)?;
+    assert_eq!(&data[..], &expect[..]);
+    assert_eq!(record, DataReader::deserialize_with(config, &expect)?);
+    assert_eq!(record, bincode::deserialize::<Record>(&data)?);
+    Ok(())
+}
+
+enum Nested {
+    Leaf(String),
+    Node(Vec<Nested>),
+}
+
+impl Nested {
+    fn deep(depth: usize) -> Nested {
+        if depth == 0 {
+            Nested::Leaf("x".repeat(200))
+        } else {
+            Nested::Node(vec![Nested::deep(depth - 1), Nested::Leaf("y".to_string())])
+        }
+    }
+}
+
+/// `sized` writes the lengths up front, otherwise they are reserved and patched
+struct NestedSer<'a>(&'a Nested, bool);
+
+impl Serialize for NestedSer<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Nested::Leaf(s) => serializer.serialize_str(s),
+            Nested::Node(v) if self.1 => {
+                serializer.collect_seq(v.iter().map(|n| NestedSer(n, true)))
+            }
+            Nested::Node(v) => {
+                serializer.collect_seq(v.iter().filter(|_| true).map(|n| NestedSer(n, false)))
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_deep_unknown_len() -> Result<()> {
+    use data_rw::LenEncoding;
+
+    let value = Nested::deep(64);
+    for config in [
+        Config::new(),
+        Config::new().len_encoding(LenEncoding::Varint),
+        Config::new()
+            .len_encoding(LenEncoding::Varint)
+            .skippable_fields(true),
+        Config::new().len_encoding(LenEncoding::U64),
+    ] {
+        let sized = Data::serialize_with(config, NestedSer(&value, true))?;
+        let patched = Data::serialize_with(config, NestedSer(&value, false))?;
+        assert_eq!(&sized[..], &patched[..]);
+    }
+    Ok(())
+}
@@ -1,3 +1,6 @@
+// the baseline tests pass values by reference on purpose
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use anyhow::Result;
 use data_rw::{Data, DataReader};
 
@@ -129,3 +132,89 @@ fn test_read() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_len_encoding() -> Result<()> {
+    use data_rw::{Config, DataOwnedReader, LenEncoding};
+
+    for (encoding, size) in [
+        (LenEncoding::U32, 4),
+        (LenEncoding::U64, 8),
+        (LenEncoding::Varint, 1),
+    ] {
+        let config = Config::new().len_encoding(encoding);
+        let mut data = Data::new();
+        data.set_config(config);
+        data.write_fixed("hello");
+        data.try_write_fixed(&[1u8, 2, 3][..])?;
+        assert_eq!(data.len(), size * 2 + 8);
+        data.write_fixed_at(0, "world")?;
+
+        let mut rd = DataReader::from(&data);
+        rd.set_config(config);
+        assert_eq!(rd.read_fixed_str()?, "world");
+        assert_eq!(rd.read_fixed_buf()?, [1, 2, 3]);
+        assert!(rd.is_empty());
+
+        let mut rd = DataOwnedReader::new(data.into_inner());
+        rd.set_config(config);
+        assert_eq!(rd.read_fixed_str()?, "world");
+        assert_eq!(rd.read_fixed_buf()?, [1, 2, 3]);
+        assert_eq!(rd.get_offset(), rd.len());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_legacy_layout() -> Result<()> {
+    use data_rw::DataOwnedReader;
+
+    let mut data = Data::new();
+    data.write_uint_n(0x123456, 3)?;
+    data.write_int_n(-2, 6)?;
+    data.write_padded_str("bob", 6, 0)?;
+    data.write_padded_str("ab", 4, b' ')?;
+    data.write_cstr("hi")?;
+    data.write_uint_n(u64::MAX, 8)?;
+
+    let mut expect = if cfg!(feature = "big_endian") {
+        vec![0x12, 0x34, 0x56, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe]
+    } else {
+        vec![0x56, 0x34, 0x12, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff]
+    };
+    expect.extend_from_slice(b"bob\0\0\0ab  hi\0");
+    expect.extend_from_slice(&[0xff; 8]);
+    assert_eq!(&data[..], &expect[..]);
+
+    let mut rd = DataReader::from(&data);
+    assert_eq!(rd.read_uint_n(3)?, 0x123456);
+    assert_eq!(rd.read_int_n(6)?, -2);
+    assert_eq!(rd.read_padded_str(6)?, "bob");
+    assert_eq!(rd.read_padded_str(4)?, "ab  ");
+    assert_eq!(rd.read_cstr()?, "hi");
+    assert_eq!(rd.read_int_n(8)?, -1);
+    assert!(rd.is_empty());
+
+    let mut rd = DataOwnedReader::new(data.into_inner());
+    assert_eq!(rd.read_uint_n(3)?, 0x123456);
+    assert_eq!(rd.read_int_n(6)?, -2);
+    assert_eq!(rd.read_padded_str(6)?, "bob");
+    assert_eq!(rd.read_padded_str(4)?.trim_end_matches(' '), "ab");
+    assert_eq!(rd.read_cstr()?, "hi");
+    assert_eq!(rd.read_uint_n(8)?, u64::MAX);
+
+    let mut data = Data::new();
+    assert!(data.write_uint_n(1 << 24, 3).is_err());
+    assert!(data.write_uint_n(1, 9).is_err());
+    assert!(data.write_int_n(-(1 << 23) - 1, 3).is_err());
+    assert!(data.write_int_n(1 << 23, 3).is_err());
+    data.write_int_n(-(1 << 23), 3)?;
+    assert!(data.write_padded_str("toolong", 4, 0).is_err());
+    assert!(data.write_cstr("a\0b").is_err());
+    assert_eq!(data.len(), 3);
+
+    assert!(DataReader::from(&[1, 2]).read_uint_n(3).is_err());
+    assert!(DataReader::from(&[1, 2]).read_int_n(0).is_err());
+    assert!(DataReader::from(b"abc").read_cstr().is_err());
+    Ok(())
+}
@@ -1,3 +1,6 @@
+// kept as the baseline tests were written
+#![allow(clippy::bool_assert_comparison, clippy::useless_conversion)]
+
 use anyhow::Result;
 use data_rw::data_owned_reader::DataOwnedReader;
 use data_rw::{Data, DataReader};
@@ -556,3 +559,197 @@ pub fn test_owned_serde_de() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+pub fn test_serde_size_hint() -> Result<()> {
+    {
+        let list = (0..10000u32).collect::<Vec<_>>();
+        let mut map = HashMap::new();
+        for i in 0..1000u32 {
+            map.insert(i, i.to_string());
+        }
+        let mut data = Data::new();
+        data.serde_serialize(&list)?;
+        data.serde_serialize(&map)?;
+
+        let mut rdata = DataReader::from(&data[..]);
+        let v = rdata.serde_deserialize::<Vec<u32>>()?;
+        assert_eq!(list, v);
+        assert!(v.capacity() < list.len() * 2);
+        assert_eq!(map, rdata.serde_deserialize::<HashMap<u32, String>>()?);
+    }
+    {
+        let mut data = Data::new();
+        data.write_fixed(u32::MAX);
+        data.write_fixed(1u64);
+        let mut rdata = DataReader::from(&data[..]);
+        assert!(rdata.serde_deserialize::<Vec<u64>>().is_err());
+        let mut rdata = DataReader::from(&data[..]);
+        assert!(rdata.serde_deserialize::<HashMap<u8, u8>>().is_err());
+    }
+    Ok(())
+}
+
+#[test]
+pub fn test_serde_deserialize_into() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Default)]
+    struct State {
+        name: String,
+        list: Vec<u32>,
+        tags: Vec<String>,
+    }
+
+    let a = State {
+        name: "hello world".to_string(),
+        list: (0..100).collect(),
+        tags: vec!["a".to_string(), "b".to_string()],
+    };
+    let b = State {
+        name: "foo".to_string(),
+        list: (0..50).collect(),
+        tags: vec!["c".to_string()],
+    };
+
+    let mut state = State::default();
+    let data = Data::serialize(&a)?;
+    DataReader::from(&data).deserialize_into(&mut state)?;
+    assert_eq!(a, state);
+
+    let name_ptr = state.name.as_ptr();
+    let list_ptr = state.list.as_ptr();
+    let data = Data::serialize(&b)?;
+    DataReader::from(&data).deserialize_into(&mut state)?;
+    assert_eq!(b, state);
+    assert_eq!(name_ptr, state.name.as_ptr());
+    assert_eq!(list_ptr, state.list.as_ptr());
+
+    let mut data = Data::new();
+    data.serde_serialize(&a)?;
+    data.serde_serialize(&b)?;
+    let mut rdata = DataOwnedReader::new(data.into());
+    let mut state = State::default();
+    rdata.deserialize_into(&mut state)?;
+    assert_eq!(a, state);
+    rdata.deserialize_into(&mut state)?;
+    assert_eq!(b, state);
+    Ok(())
+}
+
+#[test]
+pub fn test_serde_unknown_len() -> Result<()> {
+    use serde::ser::{SerializeMap, SerializeSeq};
+    use serde::Serializer;
+
+    struct Iter(u32);
+    impl Serialize for Iter {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(None)?;
+            for i in 0..self.0 {
+                seq.serialize_element(&i)?;
+            }
+            seq.end()
+        }
+    }
+
+    struct Map(u32);
+    impl Serialize for Map {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(None)?;
+            for i in 0..self.0 {
+                map.serialize_entry(&i.to_string(), &Iter(i))?;
+            }
+            map.end()
+        }
+    }
+
+    let mut data = Data::new();
+    data.serde_serialize(Iter(100))?;
+    data.serde_serialize(Map(10))?;
+    data.serde_serialize(Iter(0))?;
+
+    let mut rdata = DataReader::from(&data[..]);
+    assert_eq!(
+        (0..100).collect::<Vec<u32>>(),
+        rdata.serde_deserialize::<Vec<u32>>()?
+    );
+    let map = rdata.serde_deserialize::<BTreeMap<String, Vec<u32>>>()?;
+    assert_eq!(10, map.len());
+    for (k, v) in map {
+        assert_eq!(k.parse::<u32>()? as usize, v.len());
+    }
+    assert!(rdata.serde_deserialize::<Vec<u32>>()?.is_empty());
+    assert!(rdata.is_empty());
+    Ok(())
+}
+
+#[test]
+pub fn test_serde_skip_field() -> Result<()> {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    struct Foo {
+        a: u32,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        b: Option<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty", default)]
+        c: Vec<u8>,
+        d: u8,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    enum E {
+        S {
+            #[serde(skip_serializing_if = "Option::is_none", default)]
+            r: Option<u8>,
+            g: u8,
+        },
+    }
+
+    let foo1 = Foo {
+        a: 1,
+        b: None,
+        c: vec![],
+        d: 2,
+    };
+    let foo2 = Foo {
+        a: 3,
+        b: Some("test".to_string()),
+        c: vec![],
+        d: 4,
+    };
+    let e = E::S { r: None, g: 5 };
+
+    struct Manual;
+    impl Serialize for Manual {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeStruct;
+            let mut s = serializer.serialize_struct("Foo", 4)?;
+            s.serialize_field("a", &5u32)?;
+            s.skip_field("b")?;
+            s.skip_field("c")?;
+            s.serialize_field("d", &6u8)?;
+            s.end()
+        }
+    }
+
+    let mut data = Data::new();
+    data.serde_serialize(&foo1)?;
+    data.serde_serialize(&foo2)?;
+    data.serde_serialize(&e)?;
+    data.serde_serialize(Manual)?;
+    data.serde_serialize(100u8)?;
+
+    let mut rdata = DataReader::from(&data[..]);
+    assert_eq!(foo1, rdata.serde_deserialize::<Foo>()?);
+    assert_eq!(foo2, rdata.serde_deserialize::<Foo>()?);
+    assert_eq!(e, rdata.serde_deserialize::<E>()?);
+    assert_eq!(
+        Foo {
+            a: 5,
+            b: None,
+            c: vec![],
+            d: 6
+        },
+        rdata.serde_deserialize::<Foo>()?
+    );
+    assert_eq!(100u8, rdata.serde_deserialize::<u8>()?);
+    Ok(())
+}
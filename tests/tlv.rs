@@ -0,0 +1,130 @@
+use anyhow::Result;
+use data_rw::tlv::{TlvFormat, TlvWidth};
+use data_rw::{Data, DataReader};
+
+#[test]
+fn test_tlv_nested() -> Result<()> {
+    let format = TlvFormat::new(TlvWidth::U8, TlvWidth::U16).big_endian(true);
+    let mut data = Data::new();
+    data.write_tlv(format, 1, b"abc")?;
+    data.write_tlv_container(format, 2, |data| {
+        data.write_tlv(format, 3, &[9])?;
+        data.write_tlv(format, 4, &[])?;
+        Ok(())
+    })?;
+    data.write_tlv(format, 5, &[1, 2])?;
+
+    let mut expect = vec![1, 0, 3, b'a', b'b', b'c'];
+    expect.extend_from_slice(&[2, 0, 7, 3, 0, 1, 9, 4, 0, 0]);
+    expect.extend_from_slice(&[5, 0, 2, 1, 2]);
+    assert_eq!(&data[..], &expect[..]);
+
+    let mut rd = DataReader::from(&data);
+    let (mut name, mut inner, mut count) = (vec![], vec![], 0);
+    for record in rd.tlv_iter(format) {
+        let (tag, mut value) = record?;
+        count += 1;
+        match tag {
+            1 => name = value.to_vec(),
+            2 => {
+                for record in value.tlv_iter(format) {
+                    let (tag, value) = record?;
+                    inner.push((tag, value.to_vec()));
+                }
+            }
+            // unknown tags are skipped
+            _ => {}
+        }
+    }
+    assert!(rd.is_empty());
+    assert_eq!(count, 3);
+    assert_eq!(name, b"abc");
+    assert_eq!(inner, vec![(3, vec![9]), (4, vec![])]);
+    Ok(())
+}
+
+#[test]
+fn test_tlv_widths() -> Result<()> {
+    let format = TlvFormat::new(TlvWidth::Varint, TlvWidth::Varint);
+    let mut data = Data::new();
+    data.write_tlv_container(format, 300, |data| {
+        data.write_buf(&[7; 200]);
+        Ok(())
+    })?;
+    assert_eq!(&data[..4], &[0xac, 0x02, 0xc8, 0x01]);
+    assert_eq!(data.len(), 204);
+    let (tag, value) = DataReader::from(&data).read_tlv(format)?;
+    assert_eq!((tag, value.len()), (300, 200));
+
+    let format = TlvFormat::new(TlvWidth::U16, TlvWidth::U32);
+    let mut data = Data::new();
+    data.write_tlv(format, 0x0102, &[0xff])?;
+    assert_eq!(&data[..], &[0x02, 0x01, 1, 0, 0, 0, 0xff]);
+
+    // tag or length too large for the width
+    let format = TlvFormat::new(TlvWidth::U8, TlvWidth::U8);
+    let mut data = Data::new();
+    assert!(data.write_tlv(format, 256, &[]).is_err());
+    assert!(data.write_tlv(format, 1, &[0; 256]).is_err());
+    assert!(data
+        .write_tlv_container(format, 1, |data| {
+            data.write_buf(&[0; 256]);
+            Ok(())
+        })
+        .is_err());
+    // nothing is left behind by a failed write
+    assert!(data.is_empty());
+
+    // a truncated value ends the iterator with an error
+    let mut rd = DataReader::from(&[1, 1, 0, 2, 5, 0]);
+    let records = rd.tlv_iter(format).collect::<Vec<_>>();
+    assert_eq!(records.len(), 2);
+    assert!(records[0].is_ok());
+    assert!(records[1].is_err());
+    Ok(())
+}
+
+#[test]
+fn test_tlv_rollback() -> Result<()> {
+    let varint = TlvFormat::new(TlvWidth::Varint, TlvWidth::Varint);
+    let short = TlvFormat::new(TlvWidth::U8, TlvWidth::U8);
+
+    let write = |data: &mut Data, fail: bool| {
+        data.write_tlv_container(varint, 1, |data| {
+            data.write_tlv(varint, 2, &[7; 200])?;
+            let res = data.write_tlv_container(varint, 3, |data| {
+                data.write_tlv_container(varint, 4, |data| {
+                    data.write_buf(&[8; 150]);
+                    Ok(())
+                })?;
+                if fail {
+                    data.write_tlv(short, 1, &[0; 256])?;
+                }
+                Ok(())
+            });
+            assert_eq!(res.is_err(), fail);
+            data.write_tlv(varint, 5, &[9; 300])
+        })
+    };
+
+    // the failed record is dropped, its neighbours keep minimal lengths
+    let mut data = Data::new();
+    write(&mut data, true)?;
+    let mut expect = Data::new();
+    expect.write_tlv_container(varint, 1, |data| {
+        data.write_tlv(varint, 2, &[7; 200])?;
+        data.write_tlv(varint, 5, &[9; 300])
+    })?;
+    assert_eq!(&data[..], &expect[..]);
+
+    let mut data = Data::new();
+    write(&mut data, false)?;
+    let (tag, mut value) = DataReader::from(&data).read_tlv(varint)?;
+    assert_eq!(tag, 1);
+    let tags = value
+        .tlv_iter(varint)
+        .map(|record| record.map(|(tag, _)| tag))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(tags, [2, 3, 5]);
+    Ok(())
+}
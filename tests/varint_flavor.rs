@@ -0,0 +1,137 @@
+use anyhow::Result;
+use data_rw::varint_flavor::VarintFlavor;
+use data_rw::{Data, DataReader};
+
+fn check(flavor: VarintFlavor, v: u64, expect: &[u8]) -> Result<()> {
+    let mut data = Data::new();
+    data.write_varint_as(flavor, v);
+    assert_eq!(&data[..], expect, "{:?} {:#x}", flavor, v);
+    assert_eq!(flavor.size(v), expect.len());
+    let mut rd = DataReader::from(&data);
+    assert_eq!(rd.read_varint_as(flavor)?, v);
+    assert!(rd.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_varint_flavor_vectors() -> Result<()> {
+    use VarintFlavor::*;
+    check(Leb128, 624485, &[0xe5, 0x8e, 0x26])?;
+    check(
+        Leb128,
+        u64::MAX,
+        &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+    )?;
+
+    // DWARF
+    for (v, expect) in [
+        (2i64, &[0x02][..]),
+        (-2, &[0x7e]),
+        (127, &[0xff, 0x00]),
+        (-127, &[0x81, 0x7f]),
+        (128, &[0x80, 0x01]),
+        (-128, &[0x80, 0x7f]),
+        (-123456, &[0xc0, 0xbb, 0x78]),
+    ] {
+        check(Sleb128, v as u64, expect)?;
+    }
+    let mut data = Data::new();
+    data.write_sleb128(i64::MIN);
+    data.write_sleb128(i64::MAX);
+    assert_eq!(data.len(), 20);
+    let mut rd = DataReader::from(&data);
+    assert_eq!(rd.read_sleb128()?, i64::MIN);
+    assert_eq!(rd.read_sleb128()?, i64::MAX);
+
+    // MIDI
+    check(Vlq, 0, &[0x00])?;
+    check(Vlq, 0x7f, &[0x7f])?;
+    check(Vlq, 0x80, &[0x81, 0x00])?;
+    check(Vlq, 0x2000, &[0xc0, 0x00])?;
+    check(Vlq, 0x3fff, &[0xff, 0x7f])?;
+    check(Vlq, 0x4000, &[0x81, 0x80, 0x00])?;
+    check(Vlq, 0x0fff_ffff, &[0xff, 0xff, 0xff, 0x7f])?;
+
+    check(GitOffset, 0x7f, &[0x7f])?;
+    check(GitOffset, 0x80, &[0x80, 0x00])?;
+    check(GitOffset, 0x407f, &[0xff, 0x7f])?;
+    check(GitOffset, 0x4080, &[0x80, 0x80, 0x00])?;
+
+    check(Prefix, 0x7f, &[0x7f])?;
+    check(Prefix, 0x80, &[0x80, 0x80])?;
+    check(Prefix, 0x3fff, &[0xbf, 0xff])?;
+    check(Prefix, 0x4000, &[0xc0, 0x40, 0x00])?;
+    check(Prefix, 1 << 56, &[0xff, 1, 0, 0, 0, 0, 0, 0, 0])?;
+    check(Prefix, u64::MAX, &[0xff; 9])?;
+
+    check(MySql, 250, &[0xfa])?;
+    check(MySql, 251, &[0xfc, 0xfb, 0x00])?;
+    check(MySql, 0x1_0000, &[0xfd, 0x00, 0x00, 0x01])?;
+    check(MySql, 0x100_0000, &[0xfe, 0, 0, 0, 1, 0, 0, 0, 0])?;
+    Ok(())
+}
+
+#[test]
+fn test_varint_flavor_sizes() -> Result<()> {
+    use VarintFlavor::*;
+    let mut values = vec![0, u64::MAX, i64::MIN as u64, i64::MAX as u64];
+    for shift in 0..64 {
+        values.extend_from_slice(&[1 << shift, (1 << shift) - 1, (1u64 << shift).wrapping_neg()]);
+    }
+    for flavor in [Leb128, Sleb128, Vlq, GitOffset, Prefix, MySql] {
+        let mut data = Data::new();
+        for &v in &values {
+            let len = data.len();
+            data.write_varint_as(flavor, v);
+            assert_eq!(data.len() - len, flavor.size(v), "{:?} {:#x}", flavor, v);
+        }
+        let mut rd = DataReader::from(&data);
+        for &v in &values {
+            assert_eq!(rd.read_varint_as(flavor)?, v, "{:?}", flavor);
+        }
+        assert!(rd.is_empty());
+    }
+
+    // prefix keys sort like their values
+    let mut keys = values
+        .iter()
+        .map(|&v| {
+            let mut data = Data::new();
+            data.write_varint_as(Prefix, v);
+            (data.into_inner(), v)
+        })
+        .collect::<Vec<_>>();
+    keys.sort();
+    assert!(keys.windows(2).all(|w| w[0].1 <= w[1].1));
+    Ok(())
+}
+
+#[test]
+fn test_varint_flavor_errors() {
+    use VarintFlavor::*;
+    let mut overflow = vec![0xff; 9];
+    overflow.push(0x02);
+    assert!(DataReader::from(&overflow).read_varint_as(Leb128).is_err());
+    overflow[9] = 0x01;
+    assert!(DataReader::from(&overflow).read_varint_as(Sleb128).is_err());
+    assert!(DataReader::from(&[0x81; 11]).read_varint_as(Vlq).is_err());
+    assert!(DataReader::from(&[0xff; 11])
+        .read_varint_as(GitOffset)
+        .is_err());
+    assert!(DataReader::from(&[0xfb]).read_varint_as(MySql).is_err());
+    assert!(DataReader::from(&[0xff]).read_varint_as(MySql).is_err());
+
+    // truncated input consumes nothing
+    for (flavor, buff) in [
+        (Leb128, &[0x80][..]),
+        (Sleb128, &[0x80, 0x80]),
+        (Vlq, &[0x81]),
+        (GitOffset, &[0x80]),
+        (Prefix, &[0xc0, 0x00]),
+        (MySql, &[0xfd, 0x00, 0x00]),
+    ] {
+        let mut rd = DataReader::from(buff);
+        assert!(rd.read_varint_as(flavor).is_err(), "{:?}", flavor);
+        assert_eq!(rd.len(), buff.len());
+    }
+}